@@ -0,0 +1,109 @@
+// Times genmap_fissure against the old version that cut the grid cell by
+// cell. The mapgen tests check both give the same heights.
+//
+//     cargo bench --bench fissure
+
+use std::time::{Duration, Instant};
+
+use gloamwood::{grid::Grid, mapgen::genmap_fissure, rng::Rng};
+
+const SIZES: [(usize, usize); 5] = [(16, 16), (30, 16), (64, 64), (128, 128), (256, 256)];
+
+// genmap_fissure as it was, every cut touching half the grid
+fn fissure_per_cell(terrain: &mut [Vec<f32>], rng: &mut Rng) {
+    let h = terrain.len();
+    let w = terrain[0].len();
+
+    let times = 1000;
+
+    for _ in 0..times {
+        let p1 = (rng.gen_range(0, w) as i16, rng.gen_range(0, h) as i16);
+
+        let mut p2 = p1;
+        while p1 == p2 {
+            p2 = (
+                rng.gen_range(0, w - 1) as i16,
+                rng.gen_range(0, h - 1) as i16,
+            );
+        }
+
+        let dx = p2.0 as f32 - p1.0 as f32;
+        let dy = p2.1 as f32 - p1.1 as f32;
+
+        // check if line more vertical or horizontal
+        if dy.abs() > dx.abs() {
+            // x = my + b
+            // b = x - my
+            let m = dx / dy;
+            let b = p1.0 as f32 - m * p1.1 as f32;
+
+            let mut y: usize = 0;
+            let mut x = b;
+
+            while y < h {
+                let xx = x.round() as usize;
+                let (left, right) = terrain[y].split_at_mut(xx.min(w));
+                let sign = if p1.1 > p2.1 { 1.0 } else { -1.0 };
+                for v in left {
+                    *v += sign;
+                }
+                for v in right {
+                    *v -= sign;
+                }
+
+                y += 1;
+                x += m;
+            }
+        } else {
+            // y = mx + b
+            // b = y - mx
+            let m = dy / dx;
+            let b = p1.1 as f32 - m * p1.0 as f32;
+
+            let mut x: usize = 0;
+            let mut y = b;
+
+            while x < w {
+                let yy = y.round() as usize;
+                let (top, bottom) = terrain.split_at_mut(yy.min(h));
+                let sign = if p1.0 > p2.0 { 1.0 } else { -1.0 };
+                for row in top {
+                    row[x] += sign;
+                }
+                for row in bottom {
+                    row[x] -= sign;
+                }
+
+                x += 1;
+                y += m;
+            }
+        }
+    }
+
+    for row in terrain.iter_mut() {
+        for v in row.iter_mut() {
+            *v /= times as f32;
+        }
+    }
+}
+
+fn time(mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    let mut runs = 0;
+    while runs < 3 || start.elapsed() < Duration::from_millis(300) {
+        f();
+        runs += 1;
+    }
+    start.elapsed() / runs
+}
+
+fn main() {
+    for (w, h) in SIZES {
+        let old = time(|| fissure_per_cell(&mut vec![vec![0f32; w]; h], &mut Rng::new(1)));
+        let new = time(|| genmap_fissure(&mut Grid::new(w, h, 0f32), &mut Rng::new(1)));
+        println!(
+            "{w:>3}x{h:<3} per cell {old:>10.2?}  prefix sums {new:>10.2?}  {:>6.1}x",
+            old.as_secs_f64() / new.as_secs_f64()
+        );
+    }
+}
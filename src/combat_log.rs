@@ -0,0 +1,184 @@
+use std::collections::VecDeque;
+
+use crate::{
+    entities::{Breed, Consumable, Perk, Weapon},
+    event::Event,
+    rules::{rules, Loot, Matchup},
+};
+
+// oldest lines fall off past this
+const MAX_ENTRIES: usize = 200;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Fight {
+        breed: Breed,
+        weapon: Weapon,
+        matchup: Matchup,
+        damage: i16,
+        warded: bool,
+        left: i16,
+    },
+    Loot {
+        breed: Breed,
+        loot: Loot,
+    },
+    Broke {
+        weapon: Weapon,
+    },
+    Found {
+        item: Consumable,
+    },
+    LeveledUp {
+        level: u16,
+    },
+    Perk {
+        perk: Perk,
+    },
+    Used {
+        item: Consumable,
+    },
+    MaxHpRaised {
+        maxhp: i16,
+    },
+    NoGuessFailed,
+    Won,
+    Lost,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub kind: Kind,
+    // HP before and after, filled in by the HpChanged that follows
+    pub hp: Option<(i16, i16)>,
+}
+
+impl Entry {
+    pub fn text(&self) -> String {
+        let r = rules();
+        let line = match self.kind {
+            Kind::Fight {
+                breed,
+                weapon,
+                matchup,
+                damage,
+                warded,
+                left,
+            } => {
+                let how = match matchup {
+                    _ if warded => "the ward took the hit",
+                    Matchup::Effective => "effective",
+                    Matchup::Neutral => "neutral",
+                    Matchup::Ineffective => "ineffective, double damage",
+                };
+                let end = match left {
+                    0 => String::new(),
+                    left => format!(", it's still up with {left} HP"),
+                };
+                format!(
+                    "Fought {} with {}: {how}, took {damage}{end}",
+                    r.monster_name(breed),
+                    r.weapon_name(weapon)
+                )
+            }
+            Kind::Loot {
+                breed,
+                loot: Loot::Heal,
+            } => format!("Healed at the {}", r.monster_name(breed)),
+            Kind::Loot {
+                breed,
+                loot: Loot::Weapon(weapon),
+            } => format!(
+                "Took the {} from the {}",
+                r.weapon_name(weapon),
+                r.monster_name(breed)
+            ),
+            Kind::Loot { breed, .. } => format!("Nothing on the {}", r.monster_name(breed)),
+            Kind::Broke { weapon } => format!("The {} broke", r.weapon_name(weapon)),
+            Kind::LeveledUp { level } => format!("Reached level {level}"),
+            Kind::Perk { perk } => format!("Took {}: {}", perk.name(), perk.describe()),
+            Kind::Found { item } => format!("Found a {}", item.name()),
+            Kind::Used { item } => format!("Used a {}", item.name()),
+            Kind::MaxHpRaised { maxhp } => format!("Max HP up to {maxhp}"),
+            Kind::NoGuessFailed => {
+                "No guess-free deal found, this board may need a guess".to_string()
+            }
+            Kind::Won => "Cleared the board".to_string(),
+            Kind::Lost => "Fell".to_string(),
+        };
+
+        match self.hp {
+            Some((from, to)) => format!("{line} (HP {from} -> {to})"),
+            None => line,
+        }
+    }
+}
+
+// Fights and loot of the current game, newest last.
+#[derive(Clone, Debug, Default)]
+pub struct CombatLog {
+    pub entries: VecDeque<Entry>,
+}
+
+impl CombatLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn record(&mut self, event: &Event) {
+        let kind = match *event {
+            Event::MonsterFought {
+                breed,
+                weapon,
+                matchup,
+                damage,
+                warded,
+                left,
+                ..
+            } => Kind::Fight {
+                breed,
+                weapon,
+                matchup,
+                damage,
+                warded,
+                left,
+            },
+            Event::ItemLooted { breed, loot, .. } => Kind::Loot { breed, loot },
+            Event::WeaponBroke { weapon } => Kind::Broke { weapon },
+            Event::LeveledUp { level } => Kind::LeveledUp { level },
+            Event::PerkChosen { perk } => Kind::Perk { perk },
+            Event::ItemFound { item, .. } => Kind::Found { item },
+            Event::ItemUsed { item, .. } => Kind::Used { item },
+            Event::MaxHpRaised { maxhp } => Kind::MaxHpRaised { maxhp },
+            Event::NoGuessFailed => Kind::NoGuessFailed,
+            Event::GameWon => Kind::Won,
+            Event::GameLost => Kind::Lost,
+            Event::HpChanged { from, to } => {
+                // belongs to the fight, heal, potion or perk right before it
+                let last = self.entries.iter_mut().rev().find(|e| {
+                    matches!(
+                        e.kind,
+                        Kind::Fight { .. }
+                            | Kind::Loot { .. }
+                            | Kind::Used { .. }
+                            | Kind::Perk { .. }
+                    )
+                });
+                if let Some(entry) = last {
+                    entry.hp = Some((from, to));
+                }
+                return;
+            }
+            Event::TileOpened { .. } => return,
+        };
+
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Entry { kind, hp: None });
+    }
+}
@@ -0,0 +1,75 @@
+use std::{fs, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    mapgen::Generator,
+    placement::{self, GenError, MAX_SIDE, MIN_SIDE},
+    save::{data_dir, write_atomic, SaveError},
+    worldmap::WorldMap,
+};
+
+const CUSTOM_FILE: &str = "custom.json";
+
+// The last custom game the player set up.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Custom {
+    pub mapw: usize,
+    pub maph: usize,
+    pub mines: usize,
+    // breeds 2..=9, None keeps the ruleset share
+    pub quotas: [Option<usize>; 10],
+    #[serde(default)]
+    pub generator: Generator,
+}
+
+impl Custom {
+    // What can be told without dealing a board. Returns the full quotas,
+    // bats included.
+    pub fn check(&self) -> Result<[usize; 10], GenError> {
+        let sides = MIN_SIDE..=MAX_SIDE;
+        if !sides.contains(&self.mapw) || !sides.contains(&self.maph) {
+            return Err(GenError::BadSize {
+                mapw: self.mapw,
+                maph: self.maph,
+            });
+        }
+
+        let tiles = self.mapw * self.maph;
+        if self.mines + 9 > tiles {
+            return Err(GenError::BoardTooSmall {
+                tiles,
+                monsters: self.mines,
+            });
+        }
+
+        placement::quotas_with(self.mines, &self.quotas)
+    }
+
+    // Deals the board for real, which is the only way to know the terrain
+    // can be bent to fit every breed.
+    pub fn deal(&self, seed: u64) -> Result<WorldMap, GenError> {
+        self.check()?;
+        WorldMap::generate_with(
+            self.mapw,
+            self.maph,
+            self.mines,
+            self.quotas,
+            self.generator,
+            seed,
+        )
+    }
+}
+
+pub fn custom_path() -> PathBuf {
+    data_dir().join(CUSTOM_FILE)
+}
+
+pub fn load() -> Result<Custom, SaveError> {
+    Ok(serde_json::from_slice(&fs::read(custom_path())?)?)
+}
+
+pub fn save(custom: &Custom) -> Result<(), SaveError> {
+    write_atomic(&custom_path(), &serde_json::to_vec_pretty(custom)?)?;
+    Ok(())
+}
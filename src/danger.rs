@@ -0,0 +1,89 @@
+use crate::{
+    rng::Rng,
+    solver::{breeds, level, single, Solver},
+    worldmap::WorldMap,
+};
+
+const SAMPLES: usize = 32;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Danger {
+    // chance the tile holds a monster
+    pub monster: f32,
+    // expected level on the tile, counting empty as 0
+    pub level: f32,
+}
+
+// Per-tile odds for the closed tiles, row major. Frontier tiles come from
+// random fillings that agree with every number and flag, whatever is left
+// of the HUD counts is spread over the hidden tiles that could host each
+// breed on the terrain the player can see.
+pub fn danger(world: &WorldMap, rng: &mut Rng) -> Vec<Danger> {
+    let n = world.mapw * world.maph;
+    let mut out = vec![Danger::default(); n];
+
+    // go by the player's flags unless they can't all be right
+    let mut solver = Solver::new(world);
+    solver.assume_flags(world);
+    if !solver.solve() {
+        solver = Solver::new(world);
+        if !solver.solve() {
+            return out;
+        }
+    }
+
+    let mut fillings: Vec<Vec<u16>> = (0..SAMPLES).filter_map(|_| solver.sample(rng)).collect();
+    if fillings.is_empty() {
+        fillings.push(solver.domains.clone());
+    }
+
+    for d in &fillings {
+        let mut left = solver.counts().map(|c| c as f32);
+        for b in d.iter().filter_map(|&v| single(v)) {
+            left[b] -= 1.;
+        }
+
+        let undecided: Vec<usize> = (0..n).filter(|&t| single(d[t]).is_none()).collect();
+        let mut share = [0f32; 10];
+        for (b, s) in share.iter_mut().enumerate().skip(1) {
+            let room = undecided.iter().filter(|&&t| d[t] & 1 << b != 0).count();
+            if room > 0 {
+                *s = left[b].max(0.) / room as f32;
+            }
+        }
+
+        for (t, o) in out.iter_mut().enumerate() {
+            if world.open.as_slice()[t] {
+                continue;
+            }
+
+            if let Some(b) = single(d[t]) {
+                if b > 0 {
+                    o.monster += 1.;
+                    o.level += level(b) as f32;
+                }
+                continue;
+            }
+
+            let mut p = 0.;
+            let mut l = 0.;
+            for b in breeds(d[t]).filter(|&b| b > 0) {
+                p += share[b];
+                l += share[b] * level(b) as f32;
+            }
+            if p > 1. {
+                l /= p;
+                p = 1.;
+            }
+            o.monster += p;
+            o.level += l;
+        }
+    }
+
+    for o in out.iter_mut() {
+        o.monster /= fillings.len() as f32;
+        o.level /= fillings.len() as f32;
+    }
+
+    out
+}
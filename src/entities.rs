@@ -1,93 +1,223 @@
-#[derive(Clone, Copy, Debug)]
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+// Row in the ruleset's monster table, 0 being the hero. Only ever holds a
+// valid row so it can index per-breed tables directly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(into = "u8", try_from = "u8")]
+pub struct Breed(u8);
+
+// Row in the ruleset's weapon table, 0 being nothing in hand.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(into = "u8", try_from = "u8")]
+pub struct Weapon(u8);
+
+impl Breed {
+    pub const COUNT: usize = 10;
+    pub const HERO: Breed = Breed(0);
+    pub const BAT: Breed = Breed(1);
+
+    pub fn new(i: usize) -> Option<Self> {
+        (i < Self::COUNT).then_some(Breed(i as u8))
+    }
+
+    #[inline(always)]
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn is_monster(self) -> bool {
+        self != Self::HERO
+    }
+
+    // every breed but the hero
+    pub fn monsters() -> impl Iterator<Item = Breed> {
+        (1..Self::COUNT as u8).map(Breed)
+    }
+}
+
+impl Weapon {
+    pub const COUNT: usize = 10;
+    pub const NONE: Weapon = Weapon(0);
+    pub const UNARMED: Weapon = Weapon(1);
+
+    pub fn new(i: usize) -> Option<Self> {
+        (i < Self::COUNT).then_some(Weapon(i as u8))
+    }
+
+    #[inline(always)]
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    // every weapon but none, unarmed first
+    pub fn all() -> impl Iterator<Item = Weapon> {
+        (1..Self::COUNT as u8).map(Weapon)
+    }
+}
+
+// Used up in one go from the bag, unlike weapons.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Consumable {
+    // heals
+    Potion,
+    // opens a square without fighting what's in it
+    Scroll,
+    // shows the terrain in a square
+    Fog,
+    // the next fights cost nothing
+    Ward,
+}
+
+impl Consumable {
+    pub const COUNT: usize = 4;
+    pub const ALL: [Consumable; Self::COUNT] = [
+        Consumable::Potion,
+        Consumable::Scroll,
+        Consumable::Fog,
+        Consumable::Ward,
+    ];
+
+    #[inline(always)]
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Consumable::Potion => "potion",
+            Consumable::Scroll => "reveal scroll",
+            Consumable::Fog => "fog-lift",
+            Consumable::Ward => "ward",
+        }
+    }
+
+    // used on a tile rather than on the hero
+    pub fn aimed(self) -> bool {
+        matches!(self, Consumable::Scroll | Consumable::Fog)
+    }
+}
+
+// Picked on every hero level up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Perk {
+    // +2 max HP, and the HP to go with it
+    Vitality,
+    // every fight costs 1 less
+    Toughness,
+    // back to full HP
+    SecondWind,
+}
+
+impl Perk {
+    pub const COUNT: usize = 3;
+    pub const ALL: [Perk; Self::COUNT] = [Perk::Vitality, Perk::Toughness, Perk::SecondWind];
+
+    #[inline(always)]
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Perk::Vitality => "vitality",
+            Perk::Toughness => "toughness",
+            Perk::SecondWind => "second wind",
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Perk::Vitality => "+2 max HP",
+            Perk::Toughness => "take 1 less damage in every fight",
+            Perk::SecondWind => "heal to full",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct OutOfRange(&'static str, u8);
+
+impl fmt::Display for OutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown {} {}", self.0, self.1)
+    }
+}
+
+impl From<Breed> for u8 {
+    fn from(b: Breed) -> Self {
+        b.0
+    }
+}
+
+impl TryFrom<u8> for Breed {
+    type Error = OutOfRange;
+
+    fn try_from(i: u8) -> Result<Self, Self::Error> {
+        Self::new(i as usize).ok_or(OutOfRange("breed", i))
+    }
+}
+
+impl From<Weapon> for u8 {
+    fn from(w: Weapon) -> Self {
+        w.0
+    }
+}
+
+impl TryFrom<u8> for Weapon {
+    type Error = OutOfRange;
+
+    fn try_from(i: u8) -> Result<Self, Self::Error> {
+        Self::new(i as usize).ok_or(OutOfRange("weapon", i))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Entity {
+    #[serde(skip)]
     pub proto: Option<&'static Entity>,
-    pub breed: i16,
+    // None on empty tiles
+    #[serde(with = "breed_code")]
+    pub breed: Option<Breed>,
     pub level: i16,
     pub hp: i16,
     pub active: bool,
 }
 
+// Saves store breeds as -1 for empty, then the row number.
+mod breed_code {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use super::Breed;
+
+    pub fn serialize<S: Serializer>(breed: &Option<Breed>, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_i16(breed.map_or(-1, |b| b.index() as i16))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Breed>, D::Error> {
+        let code = i16::deserialize(d)?;
+        if code == -1 {
+            return Ok(None);
+        }
+        usize::try_from(code)
+            .ok()
+            .and_then(Breed::new)
+            .map(Some)
+            .ok_or_else(|| D::Error::custom(format!("unknown breed {code}")))
+    }
+}
+
 pub static NONE: Entity = Entity {
     proto: None,
-    breed: -1,
+    breed: None,
     level: 0,
     hp: 999,
     active: false,
 };
 
-pub static MONSTERS: [Entity; 10] = [
-    Entity {
-        proto: None,
-        breed: 0,
-        level: 0,
-        hp: 10,
-        active: false,
-    },
-    Entity {
-        proto: None,
-        breed: 1,
-        level: 1,
-        hp: 1,
-        active: false,
-    },
-    Entity {
-        proto: None,
-        breed: 2,
-        level: 2,
-        hp: 2,
-        active: false,
-    },
-    Entity {
-        proto: None,
-        breed: 3,
-        level: 3,
-        hp: 3,
-        active: false,
-    },
-    Entity {
-        proto: None,
-        breed: 4,
-        level: 4,
-        hp: 4,
-        active: false,
-    },
-    Entity {
-        proto: None,
-        breed: 5,
-        level: 5,
-        hp: 5,
-        active: false,
-    },
-    Entity {
-        proto: None,
-        breed: 6,
-        level: 6,
-        hp: 6,
-        active: false,
-    },
-    Entity {
-        proto: None,
-        breed: 7,
-        level: 7,
-        hp: 7,
-        active: false,
-    },
-    Entity {
-        proto: None,
-        breed: 8,
-        level: 8,
-        hp: 8,
-        active: false,
-    },
-    Entity {
-        proto: None,
-        breed: 9,
-        level: 9,
-        hp: 9,
-        active: false,
-    },
-];
-
 impl Entity {
     pub fn new(proto: &'static Entity) -> Self {
         Self {
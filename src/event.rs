@@ -0,0 +1,70 @@
+use crate::{
+    entities::{Breed, Consumable, Perk, Weapon},
+    rules::{Loot, Matchup},
+};
+
+// What a move did to the board, in the order it happened. WorldMap queues
+// these as it goes and whoever draws, plays sounds or keeps score drains
+// them with take_events.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    TileOpened {
+        x: usize,
+        y: usize,
+    },
+    // the hero stepped onto a monster, damage is what the hero lost
+    MonsterFought {
+        x: usize,
+        y: usize,
+        breed: Breed,
+        weapon: Weapon,
+        matchup: Matchup,
+        damage: i16,
+        // a ward took the hit
+        warded: bool,
+        // monster HP after the hero's blow, 0 if it died
+        left: i16,
+    },
+    // picked over a dead monster
+    ItemLooted {
+        x: usize,
+        y: usize,
+        breed: Breed,
+        loot: Loot,
+    },
+    // dropped by a monster or turned up on an empty tile
+    ItemFound {
+        x: usize,
+        y: usize,
+        item: Consumable,
+    },
+    // x, y is where it was aimed, the hero for the rest
+    ItemUsed {
+        x: usize,
+        y: usize,
+        item: Consumable,
+    },
+    // worn through, the hero is unarmed now
+    WeaponBroke {
+        weapon: Weapon,
+    },
+    // the hero reached a new level and is owed a perk
+    LeveledUp {
+        level: u16,
+    },
+    PerkChosen {
+        perk: Perk,
+    },
+    HpChanged {
+        from: i16,
+        to: i16,
+    },
+    MaxHpRaised {
+        maxhp: i16,
+    },
+    // no hand dealt for a no-guess board came out solvable, so the board
+    // plays as dealt and may need a guess
+    NoGuessFailed,
+    GameWon,
+    GameLost,
+}
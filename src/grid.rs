@@ -0,0 +1,226 @@
+use std::ops::{Index, IndexMut};
+use std::slice::{ChunksExact, ChunksExactMut};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+// One board layer, row after row in a single Vec. Indexed by (x, y).
+// Saved as a list of rows so files from before the flat layout still load.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Grid<T> {
+    w: usize,
+    h: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(w: usize, h: usize, fill: T) -> Self {
+        Self {
+            w,
+            h,
+            cells: vec![fill; w * h],
+        }
+    }
+
+    pub fn fill(&mut self, value: T) {
+        self.cells.fill(value);
+    }
+}
+
+impl<T> Grid<T> {
+    // None if the rows aren't all the same length
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Option<Self> {
+        let h = rows.len();
+        let w = rows.first().map_or(0, Vec::len);
+        if rows.iter().any(|row| row.len() != w) {
+            return None;
+        }
+
+        Some(Self {
+            w,
+            h,
+            cells: rows.into_iter().flatten().collect(),
+        })
+    }
+
+    // cell (x, y) is f(x, y)
+    pub fn from_fn(w: usize, h: usize, mut f: impl FnMut(usize, usize) -> T) -> Self {
+        Self {
+            w,
+            h,
+            cells: (0..w * h).map(|i| f(i % w, i / w)).collect(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn width(&self) -> usize {
+        self.w
+    }
+
+    #[inline(always)]
+    pub fn height(&self) -> usize {
+        self.h
+    }
+
+    #[inline(always)]
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x < self.w && y < self.h
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        if self.contains(x, y) {
+            self.cells.get(y * self.w + x)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut T> {
+        if self.contains(x, y) {
+            self.cells.get_mut(y * self.w + x)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.cells
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.cells.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.cells.iter_mut()
+    }
+
+    // every cell with its position, row by row
+    pub fn positions(&self) -> impl Iterator<Item = ((usize, usize), &T)> {
+        let w = self.w.max(1);
+        self.cells
+            .iter()
+            .enumerate()
+            .map(move |(i, v)| ((i % w, i / w), v))
+    }
+
+    pub fn rows(&self) -> ChunksExact<'_, T> {
+        self.cells.chunks_exact(self.w.max(1))
+    }
+
+    pub fn rows_mut(&mut self) -> ChunksExactMut<'_, T> {
+        self.cells.chunks_exact_mut(self.w.max(1))
+    }
+
+    // the 3x3 around (x, y) that's on the board, (x, y) included
+    pub fn neighbors(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> {
+        neighbors(x, y, self.w, self.h)
+    }
+
+    // same for the (2n+1)x(2n+1) square
+    pub fn neighborsn(&self, x: usize, y: usize, n: usize) -> impl Iterator<Item = (usize, usize)> {
+        neighborsn(x, y, self.w, self.h, n)
+    }
+
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid {
+            w: self.w,
+            h: self.h,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+}
+
+pub fn neighbors(x: usize, y: usize, w: usize, h: usize) -> impl Iterator<Item = (usize, usize)> {
+    neighborsn(x, y, w, h, 1)
+}
+
+// clipped to the board in usize so sides of any length work
+#[inline(always)]
+pub fn neighborsn(
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+    n: usize,
+) -> impl Iterator<Item = (usize, usize)> {
+    let xs = x.saturating_sub(n)..x.saturating_add(n + 1).min(w);
+    let ys = y.saturating_sub(n)..y.saturating_add(n + 1).min(h);
+    ys.flat_map(move |yy| xs.clone().map(move |xx| (xx, yy)))
+}
+
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    // x past the width would land on the next row, so check it too
+    #[inline(always)]
+    fn index(&self, (x, y): (usize, usize)) -> &T {
+        assert!(
+            self.contains(x, y),
+            "({x}, {y}) is off a {}x{} grid",
+            self.w,
+            self.h
+        );
+        &self.cells[y * self.w + x]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    #[inline(always)]
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut T {
+        assert!(
+            self.contains(x, y),
+            "({x}, {y}) is off a {}x{} grid",
+            self.w,
+            self.h
+        );
+        &mut self.cells[y * self.w + x]
+    }
+}
+
+impl<T: Serialize> Serialize for Grid<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.rows())
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Grid<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let rows = Vec::<Vec<T>>::deserialize(deserializer)?;
+        Grid::from_rows(rows).ok_or_else(|| de::Error::custom("grid rows differ in length"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neighbors_stay_on_the_board() {
+        let grid = Grid::new(4, 3, 0);
+        assert_eq!(
+            grid.neighbors(0, 0).collect::<Vec<_>>(),
+            vec![(0, 0), (1, 0), (0, 1), (1, 1)]
+        );
+        assert_eq!(grid.neighbors(1, 1).count(), 9);
+        assert_eq!(grid.neighbors(3, 2).count(), 4);
+        assert_eq!(grid.neighborsn(1, 1, 5).count(), 12);
+    }
+
+    // past what an i16 holds, where the old maths wrapped around
+    #[test]
+    fn neighbors_on_very_long_sides() {
+        let (w, h) = (70_000, 3);
+        assert_eq!(
+            neighbors(w - 1, 1, w, h).collect::<Vec<_>>(),
+            vec![
+                (w - 2, 0),
+                (w - 1, 0),
+                (w - 2, 1),
+                (w - 1, 1),
+                (w - 2, 2),
+                (w - 1, 2)
+            ]
+        );
+        assert_eq!(neighborsn(40_000, 1, w, h, 2).count(), 15);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    solver::{level, Solver},
+    worldmap::WorldMap,
+};
+
+const MAX_CANDIDATES: usize = 6;
+const MAX_LAYERS: usize = 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Hint {
+    // nothing lives on the tile
+    Safe {
+        pos: (usize, usize),
+        reasons: Vec<(usize, usize)>,
+        counts: bool,
+    },
+    // the tile holds exactly this breed
+    Monster {
+        pos: (usize, usize),
+        breed: usize,
+        reasons: Vec<(usize, usize)>,
+        counts: bool,
+    },
+    // nothing can be proven from what is visible
+    Stuck,
+}
+
+// Finds the next thing the player can be sure of, preferring safe tiles,
+// along with the numbered tiles that force it.
+pub fn hint(world: &WorldMap) -> Hint {
+    if !world.initialized || world.game_over != 0 {
+        return Hint::Stuck;
+    }
+
+    let base = Solver::new(world);
+    let mut full = base.clone();
+    if !full.solve() {
+        return Hint::Stuck;
+    }
+
+    let mut safe = vec![];
+    let mut monsters = vec![];
+    for y in 0..world.maph {
+        for x in 0..world.mapw {
+            if world.open[(x, y)] {
+                continue;
+            }
+            match full.known(x, y) {
+                Some(0) => safe.push((x, y, 0)),
+                // skip ones the player has already flagged right
+                Some(b) if world.flags[(x, y)] != level(b) => monsters.push((x, y, b)),
+                _ => {}
+            }
+        }
+    }
+
+    for found in [safe, monsters] {
+        let Some(&(x, y, breed)) = found.first() else {
+            continue;
+        };
+
+        // smallest explanation among the first few candidates
+        let (x, y, breed, reasons, counts) = found
+            .iter()
+            .take(MAX_CANDIDATES)
+            .filter_map(|&(x, y, b)| {
+                explain(&base, y * world.mapw + x, b).map(|(r, c)| (x, y, b, r, c))
+            })
+            .min_by_key(|(_, _, _, r, _)| r.len())
+            .unwrap_or_else(|| {
+                let t = y * world.mapw + x;
+                (x, y, breed, base.watching(t).to_vec(), false)
+            });
+
+        let reasons = reasons.iter().map(|&c| base.constraints[c].pos).collect();
+        return if breed == 0 {
+            Hint::Safe {
+                pos: (x, y),
+                reasons,
+                counts,
+            }
+        } else {
+            Hint::Monster {
+                pos: (x, y),
+                breed,
+                reasons,
+                counts,
+            }
+        };
+    }
+
+    Hint::Stuck
+}
+
+// Grow a set of numbers outwards from the tile until they alone force its
+// value, then drop any that turn out not to be needed. The second element
+// says whether the monster counts had to be used as well.
+fn explain(base: &Solver, t: usize, value: usize) -> Option<(Vec<usize>, bool)> {
+    let forces = |set: &[usize], use_counts: bool| {
+        let mut s = base.restricted(set, use_counts);
+        s.solve() && s.domains[t] == 1 << value
+    };
+
+    for use_counts in [false, true] {
+        let mut set = base.watching(t).to_vec();
+        for _ in 0..MAX_LAYERS {
+            if forces(&set, use_counts) {
+                for i in (0..set.len()).rev() {
+                    let mut fewer = set.clone();
+                    fewer.remove(i);
+                    if forces(&fewer, use_counts) {
+                        set = fewer;
+                    }
+                }
+                return Some((set, use_counts));
+            }
+
+            let mut next = set.clone();
+            for &c in &set {
+                for &tile in &base.constraints[c].tiles {
+                    for &other in base.watching(tile) {
+                        if !next.contains(&other) {
+                            next.push(other);
+                        }
+                    }
+                }
+            }
+            if next.len() == set.len() {
+                break;
+            }
+            set = next;
+        }
+    }
+
+    None
+}
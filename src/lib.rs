@@ -0,0 +1,20 @@
+// Game rules for Gloamwood. Nothing in here touches macroquad so the board
+// can be driven headless by tools, bots and tests.
+pub mod combat_log;
+pub mod custom;
+pub mod danger;
+pub mod entities;
+pub mod event;
+pub mod grid;
+pub mod hint;
+pub mod mapgen;
+pub mod placement;
+pub mod replay;
+pub mod rng;
+pub mod rules;
+pub mod save;
+pub mod solver;
+pub mod stats;
+pub mod terrain;
+pub mod undo;
+pub mod worldmap;
@@ -1,25 +1,48 @@
-use items::EFFECTIVE;
-use items::INEFFECTIVE;
+use gloamwood::combat_log;
+use gloamwood::combat_log::CombatLog;
+use gloamwood::custom;
+use gloamwood::danger;
+use gloamwood::danger::Danger;
+use gloamwood::entities::Breed;
+use gloamwood::entities::Consumable;
+use gloamwood::entities::Perk;
+use gloamwood::entities::Weapon;
+use gloamwood::event::Event;
+use gloamwood::hint;
+use gloamwood::hint::Hint;
+use gloamwood::mapgen::Generator;
+use gloamwood::placement::GenError;
+use gloamwood::replay;
+use gloamwood::replay::Action;
+use gloamwood::rng;
+use gloamwood::rules;
+use gloamwood::rules::rules;
+use gloamwood::rules::Loot;
+use gloamwood::rules::Matchup;
+use gloamwood::save;
+use gloamwood::solver;
+use gloamwood::stats;
+use gloamwood::terrain::Animation;
+use gloamwood::undo;
+use gloamwood::worldmap;
 use macroquad::input;
 use macroquad::prelude::*;
 use macroquad::time;
 use macroquad::ui::hash;
 use macroquad::ui::root_ui;
 use macroquad::ui::Skin;
-mod entities;
-mod items;
-mod mapgen;
-mod spawns;
-mod worldmap;
 
-const Si: i16 = 16;
-const S: f32 = Si as f32;
+const S: f32 = 16.;
 
 const OUTER_BG_COLOR: Color = color_u8!(10, 10, 15, 255);
 const BG_COLOR: Color = color_u8!(25, 25, 37, 255);
 const TERRAIN_TINT: Color = color_u8!(255, 255, 255, 220);
 
 const FOG_LINE: f32 = 1.;
+// window height past the board: 50 for the HUD on top, then the recent
+// fights and the monster counts underneath. Rows the HUD wraps onto on
+// narrow boards come on top of this.
+const HUD_H: f32 = 150.;
 
 static GAME_MODES: [(usize, usize, usize); 4] = [
     (16, 16, 64),  // small
@@ -31,8 +54,30 @@ static GAME_MODES: [(usize, usize, usize); 4] = [
 #[macroquad::main("Gloamwood")]
 async fn main() {
     set_default_filter_mode(FilterMode::Nearest);
+    prevent_quit();
     let font = load_ttf_font("assets/SyneMono-Regular.ttf").await.unwrap();
     let uifont = load_ttf_font("assets/SyneMono-Regular.ttf").await.unwrap();
+
+    // Rules come from a file so they can change without a rebuild. Bad ones
+    // stay on screen until the window is closed.
+    match rules::load(std::path::Path::new("assets/rules.json")) {
+        Ok(r) => {
+            rules::install(r);
+        }
+        Err(e) => {
+            println!("{e}");
+            loop {
+                if input::is_key_pressed(KeyCode::Escape) || is_quit_requested() {
+                    return;
+                }
+                clear_background(OUTER_BG_COLOR);
+                for (i, line) in format!("assets/rules.json: {e}").lines().enumerate() {
+                    draw_text(line, 10., 30. + 20. * i as f32, 20., WHITE);
+                }
+                next_frame().await;
+            }
+        }
+    }
     let tiles_tex = load_texture("assets/tiles.png").await.unwrap();
     let chars_tex = load_texture("assets/chars.png").await.unwrap();
     let interface_tex = load_texture("assets/interface.png").await.unwrap();
@@ -60,27 +105,18 @@ async fn main() {
     // ██║██║ ╚████║██║   ██║
     // ╚═╝╚═╝  ╚═══╝╚═╝   ╚═╝
     //
-    let init = |mapw: usize, maph: usize, mines: usize, seed_counter: u64| {
-        request_new_screen_size(mapw as f32 * S * scale, maph as f32 * S * scale + 100.);
-
-        let mut genterrains = vec![vec![0f32; mapw]; maph];
-        mapgen::genmap_fissure(&mut genterrains, seed_counter);
-        // println!("{:?}", &genterrains);
-
-        let terrains: Vec<Vec<i16>> = genterrains
-            .iter()
-            .map(|row| {
-                row.iter()
-                    // .map(|c| (c.abs() * 90.).round() as i16)
-                    .map(|c| ((c.max(-0.06) + 0.06) * 60.).round().min(10.) as i16)
-                    .collect()
-            })
-            .collect();
-
-        let mut w = worldmap::WorldMap::new(mapw, maph);
-        w.set_terrain(terrains);
-        w.init(mines, seed_counter);
-        w
+    let init = |mapw: usize,
+                maph: usize,
+                mines: usize,
+                quotas: [Option<usize>; 10],
+                generator: Generator,
+                seed: u64,
+                no_guess: bool,
+                undo: bool| {
+        let mut w = worldmap::WorldMap::generate_with(mapw, maph, mines, quotas, generator, seed)?;
+        w.no_guess = no_guess;
+        w.undo = undo;
+        Ok::<_, GenError>(w)
     };
 
     // UI Skin
@@ -111,61 +147,248 @@ async fn main() {
     };
     root_ui().push_skin(&ui_skin);
 
-    let monster_textures: Vec<Texture2D> = (0..=9)
-        .map(|i| {
-            let tex = Texture2D::from_image(&chars_tex.get_texture_data().sub_image(Rect::new(
-                16. * i as f32,
-                0.,
-                16.,
-                16.,
-            )));
-            tex.set_filter(FilterMode::Nearest);
-            tex
-        })
-        .collect();
-
-    let weapon_textures: Vec<Texture2D> = (0..=9)
-        .map(|i| {
-            let tex = Texture2D::from_image(&chars_tex.get_texture_data().sub_image(Rect::new(
-                16. * i as f32,
-                16.,
-                16.,
-                16.,
-            )));
-            tex.set_filter(FilterMode::Nearest);
-            tex
-        })
-        .collect();
-
-    let mut seed_counter = 0;
-    let mut world = init(mapw, maph, mines, seed_counter);
-    // Retry until all monsters are placed.
-    // TODO: Find better way to guarantee monster placements
-    while world.incomplete {
-        seed_counter += 1;
-        world = init(mapw, maph, mines, seed_counter);
-    }
+    // indexed by breed and by weapon
+    let monster_textures: Vec<Texture2D> =
+        (0..Breed::COUNT)
+            .filter_map(Breed::new)
+            .map(|b| {
+                let tex = Texture2D::from_image(&chars_tex.get_texture_data().sub_image(
+                    Rect::new(16. * rules().monster_sprite(b) as f32, 0., 16., 16.),
+                ));
+                tex.set_filter(FilterMode::Nearest);
+                tex
+            })
+            .collect();
+
+    let weapon_textures: Vec<Texture2D> =
+        (0..Weapon::COUNT)
+            .filter_map(Weapon::new)
+            .map(|w| {
+                let tex = Texture2D::from_image(&chars_tex.get_texture_data().sub_image(
+                    Rect::new(16. * rules().weapon_sprite(w) as f32, 16., 16., 16.),
+                ));
+                tex.set_filter(FilterMode::Nearest);
+                tex
+            })
+            .collect();
+
+    let mut no_guess = false;
+    let mut allow_undo = false;
+    let mut quotas = [None; 10];
+    let mut generator = Generator::Fissure;
+    let mut world = init(
+        mapw,
+        maph,
+        mines,
+        quotas,
+        generator,
+        rng::time_seed(),
+        no_guess,
+        allow_undo,
+    )
+    .unwrap();
+
+    // custom game form, filled in from last time
+    let mut custom_open = false;
+    let last_custom = custom::load().unwrap_or(custom::Custom {
+        mapw,
+        maph,
+        mines,
+        quotas,
+        generator,
+    });
+    let mut custom_size = [
+        last_custom.mapw.to_string(),
+        last_custom.maph.to_string(),
+        last_custom.mines.to_string(),
+    ];
+    let mut custom_quotas: [String; 10] = last_custom
+        .quotas
+        .map(|q| q.map_or(String::new(), |q| q.to_string()));
+    let mut custom_gen = Generator::ALL
+        .iter()
+        .position(|&g| g == last_custom.generator)
+        .unwrap_or(0);
+    let generator_names = Generator::ALL.map(Generator::name);
+    let mut custom_msg = String::new();
+    let mut seed_text = String::new();
+    let mut hint: Option<(Hint, Vec<Danger>)> = None;
+    // freshly opened tiles and when their fog starts to lift
+    #[cfg(not(feature = "nofog"))]
+    let mut wave: Vec<((usize, usize), f64)> = vec![];
+
+    // moves of the current board, None if it was resumed without them
+    let mut recording = Some(replay::Replay::new(&world));
+    let mut rec_start = time::get_time();
+    let mut history = undo::History::new();
+    // one file per board, rewritten if an undo takes a finished game back
+    let mut rec_path = None;
+    let keep_replay = |rec: &replay::Replay, rec_path: &mut Option<std::path::PathBuf>| {
+        if rec.actions.is_empty() {
+            return;
+        }
+        match rec.write(rec_path.get_or_insert_with(|| replay::new_replay_path(rec))) {
+            Ok(()) => println!("Replay saved"),
+            Err(e) => println!("Can't save replay: {e}"),
+        }
+    };
+    // replay being watched and the live board it's standing in for
+    let mut viewing: Option<(replay::Player, worldmap::WorldMap)> = None;
+    let mut replay_list = vec![];
+
+    let save_path = save::save_path();
+    let autosave = |world: &worldmap::WorldMap, recording: Option<&replay::Replay>| {
+        // finished runs have nothing to continue
+        let saved = if world.game_over == 0 {
+            save::save(world, recording, &save_path)
+        } else {
+            save::clear(&save_path);
+            Ok(())
+        };
+        if let Err(e) = saved {
+            println!("Autosave failed: {e}");
+        }
+    };
+    let mut show_heat = false;
+    let mut heat: Option<Vec<Danger>> = None;
 
     let mut mouse_pos;
     let mut menu_open = false;
+    let mut stats_open = false;
+    let mut combat_log = CombatLog::new();
+    let mut log_open = false;
+    // a consumable picked from the bag, waiting for a tile
+    let mut aiming: Option<Consumable> = None;
+    let item_colors = [RED, BEIGE, SKYBLUE, GOLD];
+    // monster levels a weapon does well or badly against, for the HUD
+    let matchup_levels = |breeds: &[Breed]| {
+        breeds
+            .iter()
+            .map(|&b| rules().monster(b).level.to_string())
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+    // fights are green when the weapon wins outright, red when it doubles up
+    let log_tone = |entry: &combat_log::Entry| match entry.kind {
+        combat_log::Kind::Fight {
+            matchup: Matchup::Effective,
+            ..
+        } => 1,
+        combat_log::Kind::Fight {
+            matchup: Matchup::Ineffective,
+            ..
+        }
+        | combat_log::Kind::Broke { .. }
+        | combat_log::Kind::NoGuessFailed => 2,
+        combat_log::Kind::Loot { .. }
+        | combat_log::Kind::Found { .. }
+        | combat_log::Kind::Used { .. }
+        | combat_log::Kind::Perk { .. }
+        | combat_log::Kind::MaxHpRaised { .. } => 3,
+        combat_log::Kind::LeveledUp { .. } => 1,
+        _ => 0,
+    };
+    let log_colors = [WHITE, GREEN, RED, SKYBLUE];
+    let log_skins = log_colors.map(|color| Skin {
+        label_style: root_ui()
+            .style_builder()
+            .with_font(&uifont)
+            .unwrap()
+            .text_color(color)
+            .font_size(18)
+            .build(),
+        ..ui_skin.clone()
+    });
+
+    let mut stats = stats::load().unwrap_or_else(|e| {
+        println!("Can't read stats: {e}");
+        stats::Stats::default()
+    });
+    // a board only counts once, even if undo lets it end again
+    let mut counted = false;
 
-    let mut last_game_time = time::get_time();
-    let mut left_click = false;
-    let mut right_click = false;
-    let mut right_click_t = last_game_time;
-    let mut mid_click = false;
-    let mut right_down = false;
-    let mut flagged_t = last_game_time;
+    let start_time = time::get_time();
+    let mut right_click_t = start_time;
+    let mut flagged_t = start_time;
     let min_flag_cd = 0.02;
     let mut flag_cd = min_flag_cd;
+    let mut window_size = Vec2::ZERO;
     loop {
         let t = time::get_time();
 
+        // HUD layout: Menu, hotbar and matchups from the left, Restart on the
+        // right, the stats and the bag in between while they fit and on rows
+        // of their own underneath when they don't
+        let slots = rules().slots();
+        let matchups_x = 100. + slots as f32 * 36. + 4.;
+        let matchups_w = Weapon::all()
+            .flat_map(|w| [rules().effective(w), rules().ineffective(w)])
+            .map(|breeds| measure_text(&matchup_levels(breeds), None, 24, 1.).width)
+            .fold(0., f32::max);
+        let hp_line = format!(
+            "HP: {}/{}{}  Lv {} {}  {:.0}s",
+            world.hero().hp,
+            world.maxhp,
+            if world.ward > 0 {
+                format!(" ward {}", world.ward)
+            } else {
+                String::new()
+            },
+            world.hero_level,
+            match world.xp_range() {
+                (from, Some(to)) => format!("{}/{}", world.xp - from, to - from),
+                (_, None) => "max".to_string(),
+            },
+            world.play_time
+        );
+        let seed_line = if world.undo {
+            format!("Seed: {}  Undos: {}", world.seed, world.undos)
+        } else {
+            format!("Seed: {}", world.seed)
+        };
+        // sized for the longest the HP line gets, so it doesn't hop rows mid-run
+        let stats_w = measure_text(
+            &format!("HP: {0}/{0} ward 99  Lv 99 9999/9999  99999s", world.maxhp),
+            Some(&font),
+            24,
+            1.,
+        )
+        .width
+        .max(measure_text(&seed_line, Some(&font), 14, 1.).width);
+        let bag_w = Consumable::COUNT as f32 * 36.;
+
+        let mut row = 0.;
+        let mut right = screen_width() - 95.;
+        let mut stats_x = (matchups_x + matchups_w + 16.).max(screen_width() / 2.);
+        if stats_x + stats_w > right {
+            row += 1.;
+            right = screen_width();
+            stats_x = 8.;
+        }
+        let stats_y = 40. * row;
+        if stats_x + stats_w + 8. + bag_w + 8. > right {
+            row += 1.;
+            right = screen_width();
+        }
+        let bag_x = right - bag_w - 8.;
+        let bag_y = 40. * row;
+        let hud_top = 50. + 40. * row;
+
+        // the window follows the board, plus however many rows the HUD took
+        let size = vec2(
+            world.mapw as f32 * S * scale,
+            world.maph as f32 * S * scale + HUD_H + hud_top - 50.,
+        );
+        if size != window_size {
+            request_new_screen_size(size.x, size.y);
+            window_size = size;
+        }
+
         // adjust camera in case of screen size changes
         gamecam.zoom.x = 1. / screen_width() * scalex2;
         gamecam.zoom.y = 1. / screen_height() * scalex2;
         gamecam.target.x = screen_width() / scalex2;
-        gamecam.target.y = screen_height() / scalex2 - 25.;
+        gamecam.target.y = (screen_height() - 2. * hud_top) / scalex2;
 
         // ██╗███╗   ██╗██████╗ ██╗   ██╗████████╗
         // ██║████╗  ██║██╔══██╗██║   ██║╚══██╔══╝
@@ -181,15 +404,20 @@ async fn main() {
             (mouse_pos_world.y / S).floor() as i16,
         );
 
-        // Exit on escape key
-        if input::is_key_pressed(KeyCode::Escape) {
+        // Exit on escape key or window close, keeping the run for later
+        let leave_replay = viewing.is_some() && input::is_key_pressed(KeyCode::Escape);
+        if !leave_replay && (input::is_key_pressed(KeyCode::Escape) || is_quit_requested()) {
+            match &viewing {
+                Some((_, live)) => autosave(live, recording.as_ref()),
+                None => autosave(&world, recording.as_ref()),
+            }
             break;
         }
 
-        left_click = input::is_mouse_button_pressed(MouseButton::Left);
-        right_click = input::is_mouse_button_pressed(MouseButton::Right);
-        mid_click = input::is_mouse_button_pressed(MouseButton::Middle);
-        right_down = input::is_mouse_button_down(MouseButton::Right);
+        let mut left_click = input::is_mouse_button_pressed(MouseButton::Left);
+        let mut right_click = input::is_mouse_button_pressed(MouseButton::Right);
+        let mut mid_click = input::is_mouse_button_pressed(MouseButton::Middle);
+        let right_down = input::is_mouse_button_down(MouseButton::Right);
         if input::is_mouse_button_released(MouseButton::Right) {
             flag_cd = min_flag_cd;
         }
@@ -199,10 +427,17 @@ async fn main() {
 
         // Restart
         let mut r_pressed = input::is_key_pressed(KeyCode::R);
+        let mut replay_seed = None;
+        // a custom board the form has already dealt
+        let mut dealt = None;
+        let mut continue_game = false;
+        let mut watch = None;
 
         // open menu if clicked
         if root_ui().button(vec2(0., 0.), "Menu") {
             menu_open = true;
+            seed_text = world.seed.to_string();
+            replay_list = replay::list_replays();
         };
 
         if menu_open {
@@ -212,9 +447,6 @@ async fn main() {
                 vec2(600., 600.),
                 |ui| {
                     // capture mouse clicks
-                    let lclick = left_click;
-                    let rclick = right_click;
-                    let mclick = mid_click;
                     left_click = false;
                     right_click = false;
                     mid_click = false;
@@ -223,6 +455,8 @@ async fn main() {
                         for (i, (w, h, m)) in GAME_MODES.iter().enumerate() {
                             if ui.button(vec2(40. + 80. * i as f32, 40.), format!("{}x{}", w, h)) {
                                 mapw = *w;
+                                quotas = [None; 10];
+                                generator = Generator::Fissure;
                                 maph = *h;
                                 mines = *m;
                                 r_pressed = true;
@@ -234,21 +468,71 @@ async fn main() {
                         }
                     });
 
+                    if save::has_save() && ui.button(None, "Continue") {
+                        continue_game = true;
+                        menu_open = false;
+                    }
+                    if ui.button(None, "Custom game") {
+                        custom_open = true;
+                        menu_open = false;
+                    }
+                    ui.same_line(0.);
+                    if ui.button(None, "Stats") {
+                        stats_open = true;
+                        menu_open = false;
+                    }
+                    ui.same_line(0.);
+                    if ui.button(None, "Combat log (L)") {
+                        log_open = true;
+                        menu_open = false;
+                    }
+                    ui.checkbox(hash!(), "No guessing", &mut no_guess);
+                    ui.checkbox(
+                        hash!(),
+                        "Undo with Z (marks the run assisted)",
+                        &mut allow_undo,
+                    );
+                    ui.checkbox(hash!(), "Danger heatmap", &mut show_heat);
+                    ui.input_text(hash!(), "Seed", &mut seed_text);
+                    ui.same_line(0.);
+                    if ui.button(None, "Play seed") {
+                        if let Ok(seed) = seed_text.trim().parse::<u64>() {
+                            replay_seed = Some(seed);
+                            menu_open = false;
+                        }
+                    }
+
+                    if !replay_list.is_empty() {
+                        ui.label(None, "Replays");
+                    }
+                    for path in replay_list.iter().take(4) {
+                        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                        if ui.button(None, format!("Watch {name}")) {
+                            watch = Some(path.clone());
+                            menu_open = false;
+                        }
+                    }
+
                     ui.separator();
-                    for i in 1..=9 {
-                        ui.texture(monster_textures[i].weak_clone(), 32., 32.);
-                        ui.same_line(0.);
-                        ui.texture(weapon_textures[i].weak_clone(), 32., 32.);
+                    // one row per weapon, led by whoever drops it
+                    for w in Weapon::all() {
+                        for b in Breed::monsters() {
+                            if rules().loot(b) == Loot::Weapon(w) {
+                                ui.texture(monster_textures[b.index()].weak_clone(), 32., 32.);
+                                ui.same_line(0.);
+                            }
+                        }
+                        ui.texture(weapon_textures[w.index()].weak_clone(), 32., 32.);
                         ui.same_line(0.);
                         ui.button(None, "Kill:");
-                        for j in EFFECTIVE[i] {
+                        for b in rules().effective(w) {
                             ui.same_line(0.);
-                            ui.texture(monster_textures[*j as usize].weak_clone(), 32., 32.);
+                            ui.texture(monster_textures[b.index()].weak_clone(), 32., 32.);
                         }
                         ui.button(None, "Weak:");
-                        for j in INEFFECTIVE[i] {
+                        for b in rules().ineffective(w) {
                             ui.same_line(0.);
-                            ui.texture(monster_textures[*j as usize].weak_clone(), 32., 32.);
+                            ui.texture(monster_textures[b.index()].weak_clone(), 32., 32.);
                         }
                         ui.label(None, " ");
                     }
@@ -256,86 +540,513 @@ async fn main() {
             );
         }
 
+        // CUSTOM game form, checked as it's typed and dealt for real on Play
+        if custom_open {
+            root_ui().window(
+                hash!(),
+                vec2(screen_width() / 2. - 300., screen_height() / 2. - 300.),
+                vec2(600., 600.),
+                |ui| {
+                    left_click = false;
+                    right_click = false;
+                    mid_click = false;
+
+                    ui.input_text(hash!(), "Width", &mut custom_size[0]);
+                    ui.input_text(hash!(), "Height", &mut custom_size[1]);
+                    ui.input_text(hash!(), "Monsters", &mut custom_size[2]);
+                    ui.combo_box(hash!(), "Land", &generator_names, &mut custom_gen);
+                    ui.label(None, "Per breed, blank for the usual share:");
+                    for (i, text) in custom_quotas.iter_mut().enumerate().skip(2) {
+                        ui.texture(monster_textures[i].weak_clone(), 24., 24.);
+                        ui.same_line(0.);
+                        ui.input_text(hash!("quota", i), "", text);
+                    }
+
+                    let number = |text: &str, what: &str| {
+                        text.trim()
+                            .parse::<usize>()
+                            .map_err(|_| format!("{what} needs a whole number"))
+                    };
+                    let form = (|| {
+                        let mut custom = custom::Custom {
+                            mapw: number(&custom_size[0], "Width")?,
+                            maph: number(&custom_size[1], "Height")?,
+                            mines: number(&custom_size[2], "Monsters")?,
+                            quotas: [None; 10],
+                            generator: Generator::ALL[custom_gen],
+                        };
+                        for (i, text) in custom_quotas.iter().enumerate().skip(2) {
+                            if !text.trim().is_empty() {
+                                custom.quotas[i] = Some(number(text, &format!("Breed {i}"))?);
+                            }
+                        }
+                        let full = custom.check().map_err(|e| e.to_string())?;
+                        Ok::<_, String>((custom, full))
+                    })();
+
+                    match &form {
+                        Ok((_, full)) => ui.label(None, &format!("{} bats fill the rest", full[1])),
+                        Err(e) => ui.label(None, e),
+                    }
+                    if !custom_msg.is_empty() {
+                        ui.label(None, &custom_msg);
+                    }
+
+                    if ui.button(None, "Play") {
+                        if let Ok((custom, _)) = &form {
+                            let seed = rng::time_seed();
+                            match custom.deal(seed) {
+                                Ok(w) => {
+                                    dealt = Some(w);
+                                    (mapw, maph, mines) = (custom.mapw, custom.maph, custom.mines);
+                                    quotas = custom.quotas;
+                                    generator = custom.generator;
+                                    replay_seed = Some(seed);
+                                    custom_msg.clear();
+                                    custom_open = false;
+                                    if let Err(e) = custom::save(custom) {
+                                        println!("Can't remember custom game: {e}");
+                                    }
+                                }
+                                Err(e) => custom_msg = e.to_string(),
+                            }
+                        }
+                    }
+                    ui.same_line(0.);
+                    if ui.button(None, "Close") {
+                        custom_open = false;
+                    }
+                },
+            );
+        }
+
+        // STATS panel, one block per board size
+        if stats_open {
+            root_ui().window(
+                hash!(),
+                vec2(screen_width() / 2. - 300., screen_height() / 2. - 300.),
+                vec2(600., 600.),
+                |ui| {
+                    left_click = false;
+                    right_click = false;
+                    mid_click = false;
+
+                    if ui.button(None, "Close") {
+                        stats_open = false;
+                    }
+                    let time = |t: Option<f64>| t.map_or("-".to_string(), |t| format!("{t:.1}s"));
+
+                    let mut keys: Vec<String> = GAME_MODES
+                        .iter()
+                        .map(|&(w, h, m)| stats::mode_key(w, h, m))
+                        .collect();
+                    for key in stats.modes.keys() {
+                        if !keys.contains(key) {
+                            keys.push(key.clone());
+                        }
+                    }
+
+                    for key in keys {
+                        ui.separator();
+                        let Some(mode) = stats.modes.get(&key) else {
+                            ui.label(None, &format!("{key}: no games yet"));
+                            continue;
+                        };
+                        ui.label(
+                            None,
+                            &format!(
+                                "{key}: {} won, {} lost, max HP {}",
+                                mode.wins, mode.losses, mode.max_hp
+                            ),
+                        );
+                        ui.label(
+                            None,
+                            &format!(
+                                "Fastest {} (with undo {})",
+                                time(mode.best_time),
+                                time(mode.best_assisted)
+                            ),
+                        );
+                        ui.label(None, "Killed:");
+                        for (tex, n) in monster_textures.iter().zip(mode.kills).skip(1) {
+                            ui.same_line(0.);
+                            ui.texture(tex.weak_clone(), 24., 24.);
+                            ui.same_line(0.);
+                            ui.label(None, &n.to_string());
+                        }
+                        ui.label(None, "Fought with:");
+                        for (tex, n) in weapon_textures.iter().zip(mode.weapons).skip(1) {
+                            ui.same_line(0.);
+                            ui.texture(tex.weak_clone(), 24., 24.);
+                            ui.same_line(0.);
+                            ui.label(None, &n.to_string());
+                        }
+                    }
+                },
+            );
+        }
+
+        // COMBAT LOG, newest first
+        if input::is_key_pressed(KeyCode::L) {
+            log_open = !log_open;
+        }
+        if log_open {
+            root_ui().window(
+                hash!(),
+                vec2(screen_width() / 2. - 300., screen_height() / 2. - 200.),
+                vec2(600., 400.),
+                |ui| {
+                    left_click = false;
+                    right_click = false;
+                    mid_click = false;
+
+                    if ui.button(None, "Close") {
+                        log_open = false;
+                    }
+                    if combat_log.entries.is_empty() {
+                        ui.label(None, "Nothing fought yet");
+                    }
+                    for entry in combat_log.entries.iter().rev() {
+                        ui.push_skin(&log_skins[log_tone(entry)]);
+                        ui.label(None, &entry.text());
+                        ui.pop_skin();
+                    }
+                },
+            );
+        }
+
         // Restart button
         if root_ui().button(vec2(screen_width() - 95., 0.), "Restart") {
             r_pressed = true;
         };
 
-        if r_pressed {
-            world = init(mapw, maph, mines, seed_counter);
-            while world.incomplete {
-                seed_counter += 1;
-                world = init(mapw, maph, mines, seed_counter);
+        let new_seed = replay_seed.or(r_pressed.then(rng::time_seed));
+
+        // anything that swaps the board ends the replay being watched
+        if leave_replay || new_seed.is_some() || continue_game || watch.is_some() {
+            combat_log.clear();
+            aiming = None;
+            if let Some((_, live)) = viewing.take() {
+                world = live;
             }
         }
 
-        if world.game_over == 0 {
-            if mouse_tile.0 >= 0 && mouse_tile.1 >= 0 {
-                let x = mouse_tile.0 as usize;
-                let y = mouse_tile.1 as usize;
-                // OPEN tile
-                if left_click {
-                    // guard against accidental click
-                    if world.flags[y][x] == 0 {
-                        world.open_tile(x, y);
-                    }
+        // the board being left behind keeps its moves so far
+        if (new_seed.is_some() || continue_game) && world.game_over == 0 {
+            if let Some(rec) = &mut recording {
+                if !rec.actions.is_empty() {
+                    rec.record(t - rec_start, Action::Restart);
+                    keep_replay(rec, &mut rec_path);
                 }
+            }
+        }
+        if new_seed.is_some() || continue_game {
+            history.clear();
+            rec_path = None;
+            counted = false;
+        }
+
+        if let Some(seed) = new_seed {
+            let next = match dealt.take() {
+                Some(mut w) => {
+                    w.no_guess = no_guess;
+                    w.undo = allow_undo;
+                    Ok(w)
+                }
+                None => init(
+                    mapw, maph, mines, quotas, generator, seed, no_guess, allow_undo,
+                ),
+            };
+            match next {
+                Ok(w) => {
+                    world = w;
+                    recording = Some(replay::Replay::new(&world));
+                    rec_start = t;
+                }
+                Err(e) => println!("Can't start game: {e}"),
+            }
+        }
+
+        if continue_game {
+            match save::load(&save_path) {
+                Ok((w, rec)) => {
+                    (mapw, maph, mines) = (w.mapw, w.maph, w.entity_store.len() - 2);
+                    quotas = w.quota_overrides;
+                    generator = w.generator;
+                    no_guess = w.no_guess;
+                    allow_undo = w.undo;
+                    world = w;
+                    recording = rec;
+                    rec_start = t - recording.as_ref().map_or(0., |r| r.duration());
+                }
+                Err(e) => println!("Can't continue: {e}"),
+            }
+        }
+
+        if let Some(path) = watch {
+            match replay::Replay::read(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|r| replay::Player::new(r).map_err(|e| e.to_string()))
+            {
+                Ok((player, w)) => {
+                    viewing = Some((player, std::mem::replace(&mut world, w)));
+                    hint = None;
+                    heat = None;
+                }
+                Err(e) => println!("Can't watch replay: {e}"),
+            }
+        }
+
+        // REPLAY controls: space pauses, right steps, up/down change speed,
+        // left starts over
+        if let Some((player, _)) = &mut viewing {
+            let before = player.next;
+            if input::is_key_pressed(KeyCode::Space) {
+                player.paused = !player.paused;
+            }
+            if input::is_key_pressed(KeyCode::Up) {
+                player.speed = (player.speed * 2.).min(16.);
+            }
+            if input::is_key_pressed(KeyCode::Down) {
+                player.speed = (player.speed / 2.).max(0.25);
+            }
+            if input::is_key_pressed(KeyCode::Left) {
+                if let Ok((mut restart, w)) = replay::Player::new(player.replay.clone()) {
+                    restart.speed = player.speed;
+                    restart.paused = player.paused;
+                    *player = restart;
+                    world = w;
+                    heat = None;
+                }
+            }
+            if input::is_key_pressed(KeyCode::Right) {
+                player.step(&mut world);
+            } else {
+                player.advance(&mut world, time::get_frame_time() as f64);
+            }
+            if player.next != before {
+                heat = None;
+            }
+        }
+
+        // any move makes an old hint or heatmap stale
+        let mut moved = new_seed.is_some()
+            || continue_game
+            || left_click
+            || mid_click
+            || right_click
+            || input::get_last_key_pressed().is_some();
+        if moved {
+            hint = None;
+        }
+        if input::is_key_pressed(KeyCode::H) && world.game_over == 0 && viewing.is_none() {
+            let h = hint::hint(&world);
+            let odds = if h == Hint::Stuck {
+                danger::danger(&world, &mut rng::Rng::new(rng::time_seed()))
+            } else {
+                vec![]
+            };
+            hint = Some((h, odds));
+        }
+
+        // the clock starts with the first opened tile
+        if viewing.is_none() {
+            world.tick(time::get_frame_time() as f64);
+        }
 
-                // CHORD tile
-                if mid_click {
-                    world.chord_tile(x, y);
+        let mut act = |world: &mut worldmap::WorldMap, action| {
+            replay::apply(world, &mut history, action);
+            if let Some(rec) = &mut recording {
+                rec.record(t - rec_start, action);
+            }
+        };
+
+        // UNDO last move, finished games included
+        if input::is_key_pressed(KeyCode::Z) && world.undo && viewing.is_none() {
+            act(&mut world, Action::Undo);
+        }
+
+        // LEVEL UP, the board waits until a perk is picked
+        if world.perks_pending > 0 && world.game_over == 0 && viewing.is_none() {
+            let mut picked = None;
+            let level = world.hero_level - world.perks_pending + 1;
+            root_ui().window(
+                hash!(),
+                vec2(screen_width() / 2. - 200., screen_height() / 2. - 100.),
+                vec2(400., 200.),
+                |ui| {
+                    left_click = false;
+                    right_click = false;
+                    mid_click = false;
+
+                    ui.label(None, &format!("Level {level}, pick a perk"));
+                    for perk in Perk::ALL {
+                        let taken = match world.perks[perk.index()] {
+                            0 => String::new(),
+                            n => format!(" (have {n})"),
+                        };
+                        let text = format!("{}: {}{taken}", perk.name(), perk.describe());
+                        if ui.button(None, text) {
+                            picked = Some(perk);
+                        }
+                    }
+                },
+            );
+            if let Some(perk) = picked {
+                act(&mut world, Action::Perk { perk });
+            }
+        }
+
+        if world.game_over != 0 || viewing.is_some() {
+            aiming = None;
+        }
+        if right_click && aiming.take().is_some() {
+            right_click = false;
+        }
+
+        // SWITCH weapon, Tab cycles or click a hotbar slot
+        if world.game_over == 0 && viewing.is_none() && world.initialized {
+            let carried = world.inventory.len();
+            if input::is_key_pressed(KeyCode::Tab) && carried > 1 {
+                let slot = world.equipped_slot().map_or(0, |s| (s + 1) % carried);
+                act(&mut world, Action::Equip { slot });
+            }
+
+            let (mx, my) = mouse_pos;
+            if left_click && (5. ..37.).contains(&my) && mx >= 100. {
+                let slot = ((mx - 100.) / 36.) as usize;
+                if slot < slots && mx - 100. - slot as f32 * 36. < 32. {
+                    left_click = false;
+                    act(&mut world, Action::Equip { slot });
                 }
+            }
 
-                // FLAG tile
-                if right_click {
-                    world.flag_tile_inc(x, y);
-                    flagged_t = t;
-                } else if t - right_click_t > 0.2 && right_down {
-                    if t - flagged_t > flag_cd {
-                        world.flag_tile_inc(x, y);
-                        flagged_t = t;
-                        // increase cd each time
-                        flag_cd = flag_cd * 1.5 + 0.01;
+            // USE a consumable, the aimed ones wait for a tile
+            if left_click && (bag_y + 5. ..bag_y + 37.).contains(&my) && mx >= bag_x {
+                let i = ((mx - bag_x) / 36.) as usize;
+                if let Some(&item) = Consumable::ALL.get(i) {
+                    if mx - bag_x - i as f32 * 36. < 32. {
+                        left_click = false;
+                        if world.bag[i] > 0 && item.aimed() {
+                            aiming = (aiming != Some(item)).then_some(item);
+                        } else if world.bag[i] > 0 {
+                            let (x, y) = world.hero_pos;
+                            act(&mut world, Action::Use { item, x, y });
+                        }
                     }
                 }
+            }
+        }
+
+        if world.game_over == 0
+            && viewing.is_none()
+            && mouse_tile.0 >= 0
+            && mouse_tile.1 >= 0
+            && (mouse_tile.0 as usize) < world.mapw
+            && (mouse_tile.1 as usize) < world.maph
+        {
+            let x = mouse_tile.0 as usize;
+            let y = mouse_tile.1 as usize;
+            let toggle = |flag: i16, num: i16| if flag == num { 0 } else { num };
+            let mut flag = world.flags[(x, y)];
 
-                // Number key flagging
-                if input::is_key_pressed(KeyCode::Key0)
-                    || input::is_key_pressed(KeyCode::Apostrophe)
-                {
-                    world.flag_tile(x, y, 0);
-                } else if input::is_key_pressed(KeyCode::Key1) {
-                    world.flag_tile(x, y, 1);
-                } else if input::is_key_pressed(KeyCode::Key2) {
-                    world.flag_tile(x, y, 2);
-                } else if input::is_key_pressed(KeyCode::Key3) {
-                    world.flag_tile(x, y, 3);
-                } else if input::is_key_pressed(KeyCode::Key4) {
-                    world.flag_tile(x, y, 4);
-                } else if input::is_key_pressed(KeyCode::Key5) {
-                    world.flag_tile(x, y, 5);
-                } else if input::is_key_pressed(KeyCode::Key6) {
-                    world.flag_tile(x, y, 6);
-                } else if input::is_key_pressed(KeyCode::Key7) {
-                    world.flag_tile(x, y, 7);
-                } else if input::is_key_pressed(KeyCode::Key8) {
-                    world.flag_tile(x, y, 8);
-                } else if input::is_key_pressed(KeyCode::Key9) {
-                    world.flag_tile(x, y, 9);
+            // OPEN tile, or spend what's being aimed on it
+            if left_click {
+                if let Some(item) = aiming.take() {
+                    act(&mut world, Action::Use { item, x, y });
+                } else if world.flags[(x, y)] == 0 {
+                    // guard against accidental click
+                    act(&mut world, Action::Open { x, y });
                 }
             }
+
+            // CHORD tile
+            if mid_click {
+                act(&mut world, Action::Chord { x, y });
+            }
+
+            // FLAG tile
+            if right_click {
+                flag = (flag + 1) % 10;
+                flagged_t = t;
+            } else if t - right_click_t > 0.2 && right_down && t - flagged_t > flag_cd {
+                flag = (flag + 1) % 10;
+                flagged_t = t;
+                moved = true;
+                // increase cd each time
+                flag_cd = flag_cd * 1.5 + 0.01;
+            }
+
+            // Number key flagging
+            if input::is_key_pressed(KeyCode::Key0) || input::is_key_pressed(KeyCode::Apostrophe) {
+                flag = toggle(flag, 0);
+            } else if input::is_key_pressed(KeyCode::Key1) {
+                flag = toggle(flag, 1);
+            } else if input::is_key_pressed(KeyCode::Key2) {
+                flag = toggle(flag, 2);
+            } else if input::is_key_pressed(KeyCode::Key3) {
+                flag = toggle(flag, 3);
+            } else if input::is_key_pressed(KeyCode::Key4) {
+                flag = toggle(flag, 4);
+            } else if input::is_key_pressed(KeyCode::Key5) {
+                flag = toggle(flag, 5);
+            } else if input::is_key_pressed(KeyCode::Key6) {
+                flag = toggle(flag, 6);
+            } else if input::is_key_pressed(KeyCode::Key7) {
+                flag = toggle(flag, 7);
+            } else if input::is_key_pressed(KeyCode::Key8) {
+                flag = toggle(flag, 8);
+            } else if input::is_key_pressed(KeyCode::Key9) {
+                flag = toggle(flag, 9);
+            }
+
+            if flag != world.flags[(x, y)] && !world.open[(x, y)] {
+                act(&mut world, Action::Flag { x, y, value: flag });
+            }
+        }
+
+        // everything the moves above did, for stats and the drawing below
+        let events = world.take_events();
+        let ended = events
+            .iter()
+            .any(|e| matches!(e, Event::GameWon | Event::GameLost));
+        for e in &events {
+            combat_log.record(e);
+        }
+
+        if ended && viewing.is_none() {
+            if let Some(rec) = &recording {
+                keep_replay(rec, &mut rec_path);
+            }
+            if !counted {
+                counted = true;
+                stats.record(&world);
+                if let Err(e) = stats::save(&stats) {
+                    println!("Can't save stats: {e}");
+                }
+            }
+        }
+
+        if moved && viewing.is_none() {
+            heat = None;
+            autosave(&world, recording.as_ref());
         }
 
         // upate last time trackers
         if right_click {
             right_click_t = t
         }
-        last_game_time = t;
 
         clear_background(OUTER_BG_COLOR);
         set_camera(&gamecam);
 
-        draw_rectangle(0., 0., S * mapw as f32, S * maph as f32, BG_COLOR);
+        draw_rectangle(
+            0.,
+            0.,
+            S * world.mapw as f32,
+            S * world.maph as f32,
+            BG_COLOR,
+        );
 
         // ██████╗ ██████╗  █████╗ ██╗    ██╗    ████████╗███████╗██████╗ ██████╗  █████╗ ██╗███╗   ██╗
         // ██╔══██╗██╔══██╗██╔══██╗██║    ██║    ╚══██╔══╝██╔════╝██╔══██╗██╔══██╗██╔══██╗██║████╗  ██║
@@ -344,32 +1055,27 @@ async fn main() {
         // ██████╔╝██║  ██║██║  ██║╚███╔███╔╝       ██║   ███████╗██║  ██║██║  ██║██║  ██║██║██║ ╚████║
         // ╚═════╝ ╚═╝  ╚═╝╚═╝  ╚═╝ ╚══╝╚══╝        ╚═╝   ╚══════╝╚═╝  ╚═╝╚═╝  ╚═╝╚═╝  ╚═╝╚═╝╚═╝  ╚═══╝
         // TERRAIN
-        for i in 0..maph {
-            for j in 0..mapw {
-                let terrain = world.terrains[i][j];
-                let trow = terrain / 16;
-                let tmod = terrain - trow * 16;
-                let mut wavex = 0.;
-                let mut wavey = 0.;
-                if terrain == 0 {
-                    wavey = (4. * (t as f32 + i as f32 / 20. + j as f32 / 3.)).sin();
-                }
-                if terrain == 1 {
-                    wavex = (2. * (t as f32 + j as f32 / 20.)).sin() * 0.4;
-                }
+        for ((j, i), terrain) in world.terrains.positions() {
+            let props = terrain.props();
+            let trow = props.sprite / 16;
+            let tmod = props.sprite - trow * 16;
+            let (wavex, wavey) = match props.animation {
+                Animation::Still => (0., 0.),
+                Animation::Swell => (0., (4. * (t as f32 + i as f32 / 20. + j as f32 / 3.)).sin()),
+                Animation::Ripple => ((2. * (t as f32 + j as f32 / 20.)).sin() * 0.4, 0.),
+            };
 
-                draw_texture_ex(
-                    &tiles_tex,
-                    S * j as f32 + wavex,
-                    S * i as f32 + wavey,
-                    TERRAIN_TINT,
-                    DrawTextureParams {
-                        dest_size,
-                        source: Some(Rect::new(S * tmod as f32, S * trow as f32, S, S)),
-                        ..Default::default()
-                    },
-                );
-            }
+            draw_texture_ex(
+                &tiles_tex,
+                S * j as f32 + wavex,
+                S * i as f32 + wavey,
+                TERRAIN_TINT,
+                DrawTextureParams {
+                    dest_size,
+                    source: Some(Rect::new(S * tmod as f32, S * trow as f32, S, S)),
+                    ..Default::default()
+                },
+            );
         }
 
         // ██████╗ ██████╗  █████╗ ██╗    ██╗    ███╗   ███╗ ██████╗ ███╗   ██╗███████╗████████╗███████╗██████╗ ███████╗
@@ -379,29 +1085,26 @@ async fn main() {
         // ██████╔╝██║  ██║██║  ██║╚███╔███╔╝    ██║ ╚═╝ ██║╚██████╔╝██║ ╚████║███████║   ██║   ███████╗██║  ██║███████║
         // ╚═════╝ ╚═╝  ╚═╝╚═╝  ╚═╝ ╚══╝╚══╝     ╚═╝     ╚═╝ ╚═════╝ ╚═╝  ╚═══╝╚══════╝   ╚═╝   ╚══════╝╚═╝  ╚═╝╚══════╝
         // MONSTERS
-        for i in 0..maph {
-            for j in 0..mapw {
-                let eid = world.entities[i][j];
-                let ent = world.entity(j, i);
-                if ent.breed == -1 {
-                    continue;
-                }
-                let t = ent.level;
-                let trow = t / 16;
-                let tmod = t - trow * 16;
+        for ((j, i), &eid) in world.entities.positions() {
+            let ent = &world.entity_store[eid];
+            let Some(breed) = ent.breed else {
+                continue;
+            };
+            let t = rules().monster_sprite(breed);
+            let trow = t / 16;
+            let tmod = t - trow * 16;
 
-                draw_texture_ex(
-                    &chars_tex,
-                    S * j as f32,
-                    S * i as f32,
-                    WHITE,
-                    DrawTextureParams {
-                        dest_size,
-                        source: Some(Rect::new(S * tmod as f32, S * trow as f32, S, S)),
-                        ..Default::default()
-                    },
-                );
-            }
+            draw_texture_ex(
+                &chars_tex,
+                S * j as f32,
+                S * i as f32,
+                WHITE,
+                DrawTextureParams {
+                    dest_size,
+                    source: Some(Rect::new(S * tmod as f32, S * trow as f32, S, S)),
+                    ..Default::default()
+                },
+            );
         }
         set_default_camera();
 
@@ -412,56 +1115,53 @@ async fn main() {
         // ██████╔╝██║  ██║██║  ██║╚███╔███╔╝    ██║  ██║╚██████╔╝██║  ██║██║  ██║
         // ╚═════╝ ╚═╝  ╚═╝╚═╝  ╚═╝ ╚══╝╚══╝     ╚═╝  ╚═╝ ╚═════╝ ╚═╝  ╚═╝╚═╝  ╚═╝
         // AURA
-        for i in 0..maph {
-            for j in 0..mapw {
-                let t = world.auras[i][j];
-                if t == 0 {
-                    continue;
-                }
-                if world.entity(j, i).level > 0 {
-                    continue;
-                }
+        for ((j, i), &t) in world.auras.positions() {
+            if t == 0 {
+                continue;
+            }
+            if world.entity(j, i).level > 0 {
+                continue;
+            }
 
-                if world.terrains[i][j] == 8 {
-                    draw_text_ex(
-                        "?",
-                        S * 2. * j as f32 + 9.,
-                        S * 2. * i as f32 + 23. + 52.,
-                        TextParams {
-                            font: Some(&font),
-                            font_size: 24,
-                            color: WHITE,
-                            ..Default::default()
-                        },
-                    );
-                    continue;
-                }
+            if world.terrains[(j, i)].props().hides_aura {
+                draw_text_ex(
+                    "?",
+                    S * 2. * j as f32 + 9.,
+                    S * 2. * i as f32 + 23. + hud_top + 2.,
+                    TextParams {
+                        font: Some(&font),
+                        font_size: 24,
+                        color: WHITE,
+                        ..Default::default()
+                    },
+                );
+                continue;
+            }
 
-                if t < 10 {
-                    draw_text_ex(
-                        &format!("{t}"),
-                        S * 2. * j as f32 + 9.,
-                        S * 2. * i as f32 + 23. + 52.,
-                        TextParams {
-                            font: Some(&font),
-                            font_size: 24,
-                            color: Color::from_rgba(255, 255, 255, 255),
-                            ..Default::default()
-                        },
-                    );
-                } else {
-                    draw_text_ex(
-                        &format!("{t}"),
-                        S * 2. * j as f32 + 3.,
-                        S * 2. * i as f32 + 23. + 52.,
-                        TextParams {
-                            font: Some(&font),
-                            font_size: 22,
-                            color: Color::from_rgba(255, 255, 255, 255),
-                            ..Default::default()
-                        },
-                    );
-                }
+            if t < 10 {
+                draw_text_ex(
+                    &format!("{t}"),
+                    S * 2. * j as f32 + 9.,
+                    S * 2. * i as f32 + 23. + hud_top + 2.,
+                    TextParams {
+                        font: Some(&font),
+                        font_size: 24,
+                        color: Color::from_rgba(255, 255, 255, 255),
+                        ..Default::default()
+                    },
+                );
+            } else {
+                draw_text_ex(
+                    &format!("{t}"),
+                    S * 2. * j as f32 + 3.,
+                    S * 2. * i as f32 + 23. + hud_top + 2.,
+                    TextParams {
+                        font: Some(&font),
+                        font_size: 22,
+                        color: Color::from_rgba(255, 255, 255, 255),
+                        ..Default::default()
+                    },
+                );
             }
         }
 
@@ -472,94 +1172,155 @@ async fn main() {
         // ██████╔╝██║  ██║██║  ██║╚███╔███╔╝    ██║     ╚██████╔╝╚██████╔╝
         // ╚═════╝ ╚═╝  ╚═╝╚═╝  ╚═╝ ╚══╝╚══╝     ╚═╝      ╚═════╝  ╚═════╝
         // FOG
+        if show_heat && heat.is_none() && world.initialized {
+            heat = Some(danger::danger(&world, &mut rng::Rng::new(world.seed)));
+        }
         #[cfg(not(feature = "nofog"))]
         {
-            for i in 0..maph {
-                for j in 0..mapw {
-                    let t = world.open[i][j];
+            let heat = heat.as_ref().filter(|_| show_heat);
 
-                    if t == false {
-                        draw_rectangle(
-                            S * 2. * j as f32,
-                            S * 2. * i as f32 + 50.,
-                            S * 2.,
-                            S * 2.,
-                            BG_COLOR,
-                        );
+            // the fog rolls back outward from where the reveal started
+            const WAVE_STEP: f64 = 0.03;
+            const WAVE_FADE: f64 = 0.2;
+            let revealed: Vec<_> = events
+                .iter()
+                .filter_map(|e| match *e {
+                    Event::TileOpened { x, y } => Some((x, y)),
+                    _ => None,
+                })
+                .collect();
+            if let Some(&(x0, y0)) = revealed.first() {
+                for (x, y) in revealed {
+                    let dist = x.abs_diff(x0).max(y.abs_diff(y0));
+                    wave.push(((x, y), t + dist as f64 * WAVE_STEP));
+                }
+            }
+
+            for ((j, i), &t) in world.open.positions() {
+                if !t {
+                    draw_rectangle(
+                        S * 2. * j as f32,
+                        S * 2. * i as f32 + hud_top,
+                        S * 2.,
+                        S * 2.,
+                        BG_COLOR,
+                    );
+
+                    let sprite = world.terrains[(j, i)].props().sprite;
+                    draw_texture_ex(
+                        &tiles_tex,
+                        S * 2. * j as f32,
+                        S * 2. * i as f32 + hud_top,
+                        Color::from_rgba(0, 0, 0, 255),
+                        DrawTextureParams {
+                            dest_size: dest_size2,
+                            source: Some(Rect::new(0., S, S * 2., S * 2.)),
+                            ..Default::default()
+                        },
+                    );
 
-                        let terrain = world.terrains[i][j];
+                    if world.show_terrain[(j, i)] {
                         draw_texture_ex(
                             &tiles_tex,
                             S * 2. * j as f32,
-                            S * 2. * i as f32 + 50.,
-                            Color::from_rgba(0, 0, 0, 255),
+                            S * 2. * i as f32 + hud_top,
+                            Color::from_rgba(255, 255, 255, 80),
                             DrawTextureParams {
                                 dest_size: dest_size2,
-                                source: Some(Rect::new(0., S, S * 2., S * 2.)),
+                                source: Some(Rect::new(sprite as f32 * S, 0., S, S)),
                                 ..Default::default()
                             },
                         );
+                    }
 
-                        if world.show_terrain[i][j] {
-                            draw_texture_ex(
-                                &tiles_tex,
+                    // HEATMAP: redder is likelier, pinker is nastier
+                    if let Some(heat) = heat {
+                        let d = heat[i * world.mapw + j];
+                        if d.monster > 0.005 {
+                            let lvl = (d.level / d.monster).min(9.);
+                            draw_rectangle(
                                 S * 2. * j as f32,
-                                S * 2. * i as f32 + 50.,
-                                Color::from_rgba(255, 255, 255, 80),
-                                DrawTextureParams {
-                                    dest_size: dest_size2,
-                                    source: Some(Rect::new(terrain as f32 * S, 0., S, S)),
-                                    ..Default::default()
-                                },
+                                S * 2. * i as f32 + hud_top,
+                                S * 2.,
+                                S * 2.,
+                                Color::new(1., 1. - lvl / 9., lvl / 9., 0.15 + 0.6 * d.monster),
                             );
+                            if d.monster >= 0.05 {
+                                draw_text_ex(
+                                    &format!("{:.0}", lvl),
+                                    S * 2. * j as f32 + 2.,
+                                    S * 2. * i as f32 + hud_top + 11.,
+                                    TextParams {
+                                        font: Some(&font),
+                                        font_size: 12,
+                                        color: WHITE,
+                                        ..Default::default()
+                                    },
+                                );
+                            }
                         }
+                    }
 
-                        if i > 0 && world.open[i - 1][j] {
-                            draw_line(
-                                S * 2. * (j as i16) as f32,
-                                S * 2. * (i as i16) as f32 + 50.,
-                                S * 2. * (j as i16 + 1) as f32,
-                                S * 2. * (i as i16) as f32 + 50.,
-                                FOG_LINE,
-                                WHITE,
-                            )
-                        }
+                    if i > 0 && world.open[(j, i - 1)] {
+                        draw_line(
+                            S * 2. * (j as i16) as f32,
+                            S * 2. * (i as i16) as f32 + hud_top,
+                            S * 2. * (j as i16 + 1) as f32,
+                            S * 2. * (i as i16) as f32 + hud_top,
+                            FOG_LINE,
+                            WHITE,
+                        )
+                    }
 
-                        if i < world.maph - 1 && world.open[i + 1][j] {
-                            draw_line(
-                                S * 2. * (j as i16) as f32,
-                                S * 2. * (i as i16 + 1) as f32 + 50.,
-                                S * 2. * (j as i16 + 1) as f32,
-                                S * 2. * (i as i16 + 1) as f32 + 50.,
-                                FOG_LINE,
-                                WHITE,
-                            )
-                        }
+                    if world.open.get(j, i + 1) == Some(&true) {
+                        draw_line(
+                            S * 2. * (j as i16) as f32,
+                            S * 2. * (i as i16 + 1) as f32 + hud_top,
+                            S * 2. * (j as i16 + 1) as f32,
+                            S * 2. * (i as i16 + 1) as f32 + hud_top,
+                            FOG_LINE,
+                            WHITE,
+                        )
+                    }
 
-                        if j < world.mapw - 1 && world.open[i][j + 1] {
-                            draw_line(
-                                S * 2. * (j as i16 + 1) as f32,
-                                S * 2. * (i as i16) as f32 + 50.,
-                                S * 2. * (j as i16 + 1) as f32,
-                                S * 2. * (i as i16 + 1) as f32 + 50.,
-                                FOG_LINE,
-                                WHITE,
-                            )
-                        }
+                    if world.open.get(j + 1, i) == Some(&true) {
+                        draw_line(
+                            S * 2. * (j as i16 + 1) as f32,
+                            S * 2. * (i as i16) as f32 + hud_top,
+                            S * 2. * (j as i16 + 1) as f32,
+                            S * 2. * (i as i16 + 1) as f32 + hud_top,
+                            FOG_LINE,
+                            WHITE,
+                        )
+                    }
 
-                        if j > 0 && world.open[i][j - 1] {
-                            draw_line(
-                                S * 2. * (j as i16) as f32,
-                                S * 2. * (i as i16) as f32 + 50.,
-                                S * 2. * (j as i16) as f32,
-                                S * 2. * (i as i16 + 1) as f32 + 50.,
-                                FOG_LINE,
-                                WHITE,
-                            )
-                        }
+                    if j > 0 && world.open[(j - 1, i)] {
+                        draw_line(
+                            S * 2. * (j as i16) as f32,
+                            S * 2. * (i as i16) as f32 + hud_top,
+                            S * 2. * (j as i16) as f32,
+                            S * 2. * (i as i16 + 1) as f32 + hud_top,
+                            FOG_LINE,
+                            WHITE,
+                        )
                     }
                 }
             }
+
+            wave.retain(|&(_, start)| t < start + WAVE_FADE);
+            for &((j, i), start) in &wave {
+                if !world.open[(j, i)] {
+                    continue;
+                }
+                let fog = (1. - (t - start) / WAVE_FADE).min(1.) as f32;
+                draw_rectangle(
+                    S * 2. * j as f32,
+                    S * 2. * i as f32 + hud_top,
+                    S * 2.,
+                    S * 2.,
+                    Color { a: fog, ..BG_COLOR },
+                );
+            }
         }
 
         // ██████╗ ██████╗  █████╗ ██╗    ██╗    ███████╗██╗      █████╗  ██████╗ ███████╗
@@ -569,29 +1330,164 @@ async fn main() {
         // ██████╔╝██║  ██║██║  ██║╚███╔███╔╝    ██║     ███████╗██║  ██║╚██████╔╝███████║
         // ╚═════╝ ╚═╝  ╚═╝╚═╝  ╚═╝ ╚══╝╚══╝     ╚═╝     ╚══════╝╚═╝  ╚═╝ ╚═════╝ ╚══════╝
         //
-        for i in 0..maph {
-            for j in 0..mapw {
-                let t = world.flags[i][j];
-                let trow = t / 16;
-                let tmod = t - trow * 16;
+        for ((j, i), &t) in world.flags.positions() {
+            let trow = t / 16;
+            let tmod = t - trow * 16;
 
-                draw_texture_ex(
-                    &interface_tex,
-                    S * 2. * j as f32,
-                    S * 2. * i as f32 + 50.,
-                    WHITE,
-                    DrawTextureParams {
-                        dest_size: dest_size2,
-                        source: Some(Rect::new(S * tmod as f32, S * trow as f32, S, S)),
-                        ..Default::default()
-                    },
+            draw_texture_ex(
+                &interface_tex,
+                S * 2. * j as f32,
+                S * 2. * i as f32 + hud_top,
+                WHITE,
+                DrawTextureParams {
+                    dest_size: dest_size2,
+                    source: Some(Rect::new(S * tmod as f32, S * trow as f32, S, S)),
+                    ..Default::default()
+                },
+            );
+        }
+
+        // WOUNDED: HP left on monsters that lived through a fight
+        for ((j, i), &eid) in world.entities.positions() {
+            let ent = &world.entity_store[eid];
+            let Some(breed) = ent.breed.filter(|b| b.is_monster()) else {
+                continue;
+            };
+            if !world.open[(j, i)] || ent.hp < 1 || ent.hp >= rules().monster(breed).hp {
+                continue;
+            }
+            draw_text_ex(
+                &format!("{}", ent.hp),
+                S * 2. * j as f32 + 22.,
+                S * 2. * i as f32 + hud_top + 11.,
+                TextParams {
+                    font: Some(&font),
+                    font_size: 12,
+                    color: RED,
+                    ..Default::default()
+                },
+            );
+        }
+
+        // AIM: the square the picked consumable would cover
+        if let Some(item) = aiming {
+            if mouse_tile.0 >= 0 && mouse_tile.1 >= 0 {
+                let r = rules().consumable(item).power as f32;
+                draw_rectangle_lines(
+                    S * 2. * (mouse_tile.0 as f32 - r),
+                    S * 2. * (mouse_tile.1 as f32 - r) + hud_top,
+                    S * 2. * (2. * r + 1.),
+                    S * 2. * (2. * r + 1.),
+                    3.,
+                    item_colors[item.index()],
                 );
             }
         }
 
+        // ██████╗ ██████╗  █████╗ ██╗    ██╗    ██╗  ██╗██╗███╗   ██╗████████╗
+        // ██╔══██╗██╔══██╗██╔══██╗██║    ██║    ██║  ██║██║████╗  ██║╚══██╔══╝
+        // ██║  ██║██████╔╝███████║██║ █╗ ██║    ███████║██║██╔██╗ ██║   ██║
+        // ██║  ██║██╔══██╗██╔══██║██║███╗██║    ██╔══██║██║██║╚██╗██║   ██║
+        // ██████╔╝██║  ██║██║  ██║╚███╔███╔╝    ██║  ██║██║██║ ╚████║   ██║
+        // ╚═════╝ ╚═╝  ╚═╝╚═╝  ╚═╝ ╚══╝╚══╝     ╚═╝  ╚═╝╚═╝╚═╝  ╚═══╝   ╚═╝
+        // HINT
+        if let Some((h, odds)) = &hint {
+            let outline = |(x, y): (usize, usize), color: Color| {
+                draw_rectangle_lines(
+                    S * 2. * x as f32,
+                    S * 2. * y as f32 + hud_top,
+                    S * 2.,
+                    S * 2.,
+                    3.,
+                    color,
+                )
+            };
+            let because = |reasons: &[(usize, usize)], counts: bool| {
+                let numbers = match reasons.len() {
+                    0 => String::new(),
+                    1 => "the outlined number".to_string(),
+                    n => format!("the {n} outlined numbers"),
+                };
+                match (numbers.is_empty(), counts) {
+                    (true, _) => "forced by the monster counts".to_string(),
+                    (false, true) => format!("forced by {numbers} and the monster counts"),
+                    (false, false) => format!("forced by {numbers}"),
+                }
+            };
+
+            let msg = match h {
+                Hint::Safe {
+                    pos,
+                    reasons,
+                    counts,
+                } => {
+                    for &r in reasons {
+                        outline(r, YELLOW);
+                    }
+                    outline(*pos, GREEN);
+                    format!("Safe to open, {}", because(reasons, *counts))
+                }
+                Hint::Monster {
+                    pos,
+                    breed,
+                    reasons,
+                    counts,
+                } => {
+                    for &r in reasons {
+                        outline(r, YELLOW);
+                    }
+                    outline(*pos, RED);
+                    format!(
+                        "Level {} monster, {}",
+                        solver::level(*breed),
+                        because(reasons, *counts)
+                    )
+                }
+                Hint::Stuck => {
+                    for (((j, i), &open), d) in world.open.positions().zip(odds) {
+                        if open || d.monster < 0.005 {
+                            continue;
+                        }
+                        draw_text_ex(
+                            &format!("{:.0}", d.monster * 100.),
+                            S * 2. * j as f32 + 4.,
+                            S * 2. * i as f32 + hud_top + 20.,
+                            TextParams {
+                                font: Some(&font),
+                                font_size: 14,
+                                color: Color::new(1., 1. - d.monster, 1. - d.monster, 1.),
+                                ..Default::default()
+                            },
+                        );
+                    }
+                    "No sure move, numbers are the % chance of a monster".to_string()
+                }
+            };
+
+            draw_rectangle(
+                0.,
+                hud_top,
+                screen_width(),
+                24.,
+                Color::from_rgba(0, 0, 0, 200),
+            );
+            draw_text_ex(
+                &msg,
+                8.,
+                hud_top + 18.,
+                TextParams {
+                    font: Some(&font),
+                    font_size: 18,
+                    color: WHITE,
+                    ..Default::default()
+                },
+            );
+        }
+
         // #[cfg(feature = "nofog")]
         {
-            for i in 1..=9 {
+            for breed in Breed::monsters() {
+                let i = breed.index();
                 draw_text(
                     &format!("{:02}x", world.counts[i]),
                     100. * (i - 1) as f32 + 10.,
@@ -607,7 +1503,12 @@ async fn main() {
                     WHITE,
                     DrawTextureParams {
                         dest_size: dest_size2,
-                        source: Some(Rect::new(S * i as f32, S * 0 as f32, S, S)),
+                        source: Some(Rect::new(
+                            S * rules().monster_sprite(breed) as f32,
+                            S * 0 as f32,
+                            S,
+                            S,
+                        )),
                         ..Default::default()
                     },
                 );
@@ -625,14 +1526,23 @@ async fn main() {
         //     );
         // }
 
-        if world.initialized {
-            let (herox, heroy) = world.hero_pos;
-            let heroid = world.entities[heroy][herox];
+        draw_text_ex(
+            &seed_line,
+            stats_x,
+            stats_y + 40.,
+            TextParams {
+                font: Some(&font),
+                font_size: 14,
+                color: GRAY,
+                ..Default::default()
+            },
+        );
 
+        if world.initialized {
             draw_text_ex(
-                &format!("HP: {}/{}", world.hero().hp, world.maxhp),
-                screen_width() / 2.,
-                20.,
+                &hp_line,
+                stats_x,
+                stats_y + 20.,
                 TextParams {
                     font: Some(&font),
                     font_size: 24,
@@ -641,55 +1551,181 @@ async fn main() {
                 },
             );
 
-            draw_rectangle_lines(100., 5., 32., 32., 2., Color::new(1., 1., 1., 1.));
-            draw_texture_ex(
-                &chars_tex,
-                100.,
-                5.,
-                WHITE,
-                DrawTextureParams {
-                    dest_size: Some(vec2(32., 32.)),
-                    source: Some(Rect {
-                        x: world.item as f32 * S,
-                        y: S,
-                        w: S,
-                        h: S,
-                    }),
-                    ..Default::default()
-                },
-            );
+            // hotbar, the weapon in hand outlined bright
+            for slot in 0..slots {
+                let x = 100. + slot as f32 * 36.;
+                let weapon = world.inventory.get(slot);
+                let color = if weapon == Some(&world.item) {
+                    Color::new(1., 1., 1., 1.)
+                } else {
+                    Color::new(1., 1., 1., 0.3)
+                };
+                draw_rectangle_lines(x, 5., 32., 32., 2., color);
+                if let Some(&weapon) = weapon {
+                    draw_texture_ex(
+                        &chars_tex,
+                        x,
+                        5.,
+                        WHITE,
+                        DrawTextureParams {
+                            dest_size: Some(vec2(32., 32.)),
+                            source: Some(Rect {
+                                x: rules().weapon_sprite(weapon) as f32 * S,
+                                y: S,
+                                w: S,
+                                h: S,
+                            }),
+                            ..Default::default()
+                        },
+                    );
+
+                    // wear bar along the bottom, reddening as it runs out
+                    if let Some(full) = rules().durability(weapon) {
+                        let left = world.durability[weapon.index()];
+                        let frac = (left as f32 / full as f32).clamp(0., 1.);
+                        draw_rectangle(
+                            x + 2.,
+                            31.,
+                            28. * frac,
+                            4.,
+                            Color::new(1. - frac, frac, 0., 1.),
+                        );
+                        draw_text_ex(
+                            &left.to_string(),
+                            x + 2.,
+                            17.,
+                            TextParams {
+                                font: Some(&font),
+                                font_size: 12,
+                                color: WHITE,
+                                ..Default::default()
+                            },
+                        );
+                    }
+                }
+            }
+
+            // bag, dim when empty and outlined white while aiming
+            for item in Consumable::ALL {
+                let x = bag_x + item.index() as f32 * 36.;
+                let count = world.bag[item.index()];
+                let color = Color {
+                    a: if count > 0 { 1. } else { 0.3 },
+                    ..item_colors[item.index()]
+                };
+                let outline = if aiming == Some(item) { WHITE } else { color };
+                draw_rectangle(
+                    x,
+                    bag_y + 5.,
+                    32.,
+                    32.,
+                    Color {
+                        a: color.a * 0.25,
+                        ..color
+                    },
+                );
+                draw_rectangle_lines(x, bag_y + 5., 32., 32., 2., outline);
+                draw_text_ex(
+                    &item.name()[..1].to_uppercase(),
+                    x + 9.,
+                    bag_y + 29.,
+                    TextParams {
+                        font: Some(&font),
+                        font_size: 24,
+                        color,
+                        ..Default::default()
+                    },
+                );
+                if count > 0 {
+                    draw_text_ex(
+                        &count.to_string(),
+                        x + 23.,
+                        bag_y + 35.,
+                        TextParams {
+                            font: Some(&font),
+                            font_size: 12,
+                            color: WHITE,
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
 
-            draw_text(&format!("{}", world.item), 80., 30., 36., WHITE);
+            draw_text(&format!("{}", world.item.index()), 80., 30., 36., WHITE);
             draw_text(
-                &format!(
-                    "{}",
-                    EFFECTIVE[world.item]
-                        .iter()
-                        .map(|x| x.to_string())
-                        .collect::<Vec<String>>()
-                        .join(" ")
-                ),
-                140.,
+                &matchup_levels(rules().effective(world.item)),
+                matchups_x,
                 20.,
                 24.,
                 GREEN,
             );
             draw_text(
-                &format!(
-                    "{}",
-                    INEFFECTIVE[world.item]
-                        .iter()
-                        .map(|x| x.to_string())
-                        .collect::<Vec<String>>()
-                        .join(" ")
-                ),
-                140.,
+                &matchup_levels(rules().ineffective(world.item)),
+                matchups_x,
                 40.,
                 24.,
                 RED,
             );
         }
 
+        // last few fights between the board and the monster counts
+        for (k, entry) in combat_log.entries.iter().rev().take(3).enumerate() {
+            draw_text_ex(
+                &entry.text(),
+                8.,
+                screen_height() - 50. - 15. * k as f32,
+                TextParams {
+                    font: Some(&font),
+                    font_size: 14,
+                    color: Color {
+                        a: 1. - 0.3 * k as f32,
+                        ..log_colors[log_tone(entry)]
+                    },
+                    ..Default::default()
+                },
+            );
+        }
+
+        // ██████╗ ███████╗██████╗ ██╗      █████╗ ██╗   ██╗
+        // ██╔══██╗██╔════╝██╔══██╗██║     ██╔══██╗╚██╗ ██╔╝
+        // ██████╔╝█████╗  ██████╔╝██║     ███████║ ╚████╔╝
+        // ██╔══██╗██╔══╝  ██╔═══╝ ██║     ██╔══██║  ╚██╔╝
+        // ██║  ██║███████╗██║     ███████╗██║  ██║   ██║
+        // ╚═╝  ╚═╝╚══════╝╚═╝     ╚══════╝╚═╝  ╚═╝   ╚═╝
+        // REPLAY
+        if let Some((player, _)) = &viewing {
+            let state = if player.done() {
+                "done"
+            } else if player.paused {
+                "paused"
+            } else {
+                "playing"
+            };
+            draw_rectangle(
+                0.,
+                hud_top,
+                screen_width(),
+                24.,
+                Color::from_rgba(0, 0, 0, 200),
+            );
+            draw_text_ex(
+                &format!(
+                    "Replay {}/{} x{} {state}  [Space] pause [Right] step [Up/Down] speed [Left] start over [Esc] leave",
+                    player.next,
+                    player.replay.actions.len(),
+                    player.speed,
+                ),
+                8.,
+                hud_top + 18.,
+                TextParams {
+                    font: Some(&font),
+                    font_size: 14,
+                    color: WHITE,
+                    ..Default::default()
+                },
+            );
+        }
+
         if world.game_over == 1 {
             let center = get_text_center("You Win!", Option::None, 48, 1.0, 0.);
             draw_text(
@@ -710,6 +1746,19 @@ async fn main() {
             );
         }
 
+        // assisted runs say so under the result
+        if world.game_over != 0 && world.undo {
+            let msg = format!("Undo on, used {} times (Z to take back)", world.undos);
+            let center = get_text_center(&msg, Option::None, 24, 1.0, 0.);
+            draw_text(
+                &msg,
+                screen_width() / 2. - center.x,
+                screen_height() / 2. + 36.,
+                24.,
+                Color::new(1., 1., 1., 0.8),
+            );
+        }
+
         next_frame().await;
     }
 }
@@ -0,0 +1,222 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::{
+    entities::Breed,
+    rules::rules,
+    terrain::{self, Terrain},
+};
+
+const BREEDS: usize = Breed::COUNT;
+const TERRAINS: usize = terrain::COUNT;
+
+// Smallest board side that can be dealt, and the largest the custom board
+// form offers. Generation itself has no upper limit.
+pub const MIN_SIDE: usize = 5;
+pub const MAX_SIDE: usize = 64;
+
+// source, breeds 2..=9, terrains, sink
+const SRC: usize = 0;
+const SINK: usize = 1 + BREEDS + TERRAINS;
+const NODES: usize = SINK + 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GenError {
+    // width or height under MIN_SIDE, or past MAX_SIDE on the custom form
+    BadSize { mapw: usize, maph: usize },
+    // not enough tiles left over after the first click clears its 3x3
+    BoardTooSmall { tiles: usize, monsters: usize },
+    // quotas for breeds 2..=9 add up to more than the monster count
+    TooFewMonsters { monsters: usize, needed: usize },
+    // terrain couldn't be bent far enough to fit this breed's quota
+    NoRoomForBreed { breed: Breed, quota: usize },
+}
+
+impl fmt::Display for GenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenError::BadSize { mapw, maph } if mapw.min(maph) < &MIN_SIDE => write!(
+                f,
+                "a {mapw}x{maph} board won't do, sides have to be at least {MIN_SIDE}"
+            ),
+            GenError::BadSize { mapw, maph } => write!(
+                f,
+                "a {mapw}x{maph} board won't do, custom sides go up to {MAX_SIDE}"
+            ),
+            GenError::BoardTooSmall { tiles, monsters } => write!(
+                f,
+                "{monsters} monsters don't fit on a board of {tiles} tiles"
+            ),
+            GenError::TooFewMonsters { monsters, needed } => write!(
+                f,
+                "{monsters} monsters is too few, spawn quotas need at least {needed}"
+            ),
+            GenError::NoRoomForBreed { breed, quota } => {
+                write!(f, "no room for {quota} {}", rules().monster_name(*breed))
+            }
+        }
+    }
+}
+
+impl std::error::Error for GenError {}
+
+// Per-breed monster counts for a board. Breeds 2..=9 get their ruleset
+// share rounded up, bats fill whatever is left.
+pub fn quotas(mines: usize) -> Result<[usize; BREEDS], GenError> {
+    quotas_with(mines, &[None; BREEDS])
+}
+
+// Same, but breeds with an override get exactly that many.
+pub fn quotas_with(
+    mines: usize,
+    overrides: &[Option<usize>; BREEDS],
+) -> Result<[usize; BREEDS], GenError> {
+    let mut quotas = [0; BREEDS];
+    for (i, dist) in rules().spawn_dist.iter().enumerate().skip(1) {
+        quotas[i + 1] = overrides[i + 1].unwrap_or((mines as f32 / dist).ceil() as usize);
+    }
+
+    let needed: usize = quotas.iter().sum();
+    quotas[1] = mines.checked_sub(needed).ok_or(GenError::TooFewMonsters {
+        monsters: mines,
+        needed,
+    })?;
+
+    Ok(quotas)
+}
+
+// Closest terrain (by height) that the breed is allowed to spawn on.
+pub fn nearest_allowed(breed: Breed, terrain: Terrain) -> Terrain {
+    Terrain::ALL
+        .into_iter()
+        .filter(|t| t.allows(breed))
+        .min_by_key(|t| t.index().abs_diff(terrain.index()))
+        .unwrap_or(terrain)
+}
+
+// Decides how many of each breed go on each terrain type so every quota
+// (except bats, which can live anywhere) is met. This is a tiny max flow:
+// source -> breed (quota) -> terrain (if allowed) -> sink (tile count).
+//
+// Returns the first breed that can't be fully placed on failure.
+pub fn plan(
+    quotas: &[usize; BREEDS],
+    tiles: &[usize; TERRAINS],
+) -> Result<[[usize; TERRAINS]; BREEDS], Breed> {
+    let allowed = &rules().spawn_allowed;
+    let mut cap = [[0usize; NODES]; NODES];
+    for b in 2..BREEDS {
+        cap[SRC][1 + b] = quotas[b];
+        for t in 0..TERRAINS {
+            if allowed[b][t] {
+                cap[1 + b][1 + BREEDS + t] = usize::MAX;
+            }
+        }
+    }
+    for t in 0..TERRAINS {
+        cap[1 + BREEDS + t][SINK] = tiles[t];
+    }
+
+    let orig = cap;
+    while let Some(path) = augmenting_path(&cap) {
+        let mut push = usize::MAX;
+        let mut v = SINK;
+        while v != SRC {
+            let u = path[v];
+            push = push.min(cap[u][v]);
+            v = u;
+        }
+
+        let mut v = SINK;
+        while v != SRC {
+            let u = path[v];
+            cap[u][v] -= push;
+            cap[v][u] = cap[v][u].saturating_add(push);
+            v = u;
+        }
+    }
+
+    for b in Breed::monsters().skip(1) {
+        if cap[SRC][1 + b.index()] > 0 {
+            return Err(b);
+        }
+    }
+
+    let mut out = [[0; TERRAINS]; BREEDS];
+    for (b, row) in out.iter_mut().enumerate().skip(2) {
+        for (t, v) in row.iter_mut().enumerate() {
+            let (u, w) = (1 + b, 1 + BREEDS + t);
+            if orig[u][w] > 0 {
+                *v = orig[u][w] - cap[u][w];
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+fn augmenting_path(cap: &[[usize; NODES]; NODES]) -> Option<[usize; NODES]> {
+    let mut prev = [usize::MAX; NODES];
+    prev[SRC] = SRC;
+
+    let mut queue = VecDeque::from([SRC]);
+    while let Some(u) = queue.pop_front() {
+        for v in 0..NODES {
+            if prev[v] == usize::MAX && cap[u][v] > 0 {
+                prev[v] = u;
+                if v == SINK {
+                    return Some(prev);
+                }
+                queue.push_back(v);
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotas_add_up_to_the_monster_count() {
+        let quotas = quotas(120).unwrap();
+        assert_eq!(quotas[0], 0);
+        assert_eq!(quotas.iter().sum::<usize>(), 120);
+        assert!(quotas[1] > 0);
+    }
+
+    #[test]
+    fn overrides_past_the_monster_count_are_refused() {
+        let mut overrides = [None; BREEDS];
+        overrides[9] = Some(50);
+        assert!(matches!(
+            quotas_with(20, &overrides),
+            Err(GenError::TooFewMonsters { monsters: 20, .. })
+        ));
+    }
+
+    // every breed's share lands on terrain it may live on, within the tiles
+    #[test]
+    fn plan_keeps_breeds_on_their_terrain() {
+        let quotas = quotas(120).unwrap();
+        let tiles = [60; TERRAINS];
+        let plan = plan(&quotas, &tiles).unwrap();
+        for (b, row) in plan.iter().enumerate().skip(2) {
+            assert_eq!(row.iter().sum::<usize>(), quotas[b]);
+            for (t, &n) in row.iter().enumerate() {
+                assert!(n == 0 || rules().spawn_allowed[b][t]);
+            }
+        }
+        for t in 0..TERRAINS {
+            assert!(plan.iter().map(|row| row[t]).sum::<usize>() <= tiles[t]);
+        }
+    }
+
+    #[test]
+    fn plan_names_a_breed_that_doesnt_fit() {
+        let quotas = quotas(120).unwrap();
+        assert!(plan(&quotas, &[0; TERRAINS]).is_err());
+    }
+}
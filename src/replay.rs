@@ -0,0 +1,309 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    entities::{Consumable, Perk},
+    mapgen::Generator,
+    placement::GenError,
+    save::{data_dir, write_atomic, SaveError},
+    undo::History,
+    worldmap::WorldMap,
+};
+
+// older moves would play out differently once these went in:
+// 2 wore weapons down and broke them, 3 gave monsters HP, 4 put weapon
+// damage back to one blow on the usual matchups
+pub const REPLAY_VERSION: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    Open {
+        x: usize,
+        y: usize,
+    },
+    Chord {
+        x: usize,
+        y: usize,
+    },
+    // flag value after the action, 0 clears it
+    Flag {
+        x: usize,
+        y: usize,
+        value: i16,
+    },
+    // switch to the weapon in that inventory slot
+    Equip {
+        slot: usize,
+    },
+    // a consumable from the bag, aimed at x, y
+    Use {
+        item: Consumable,
+        x: usize,
+        y: usize,
+    },
+    // spend a level up
+    Perk {
+        perk: Perk,
+    },
+    Undo,
+    Restart,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Timed {
+    // seconds since the board was dealt
+    pub t: f64,
+    pub action: Action,
+}
+
+// Everything needed to deal the same board again plus what the player did
+// to it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub mapw: usize,
+    pub maph: usize,
+    pub mines: usize,
+    pub seed: u64,
+    pub no_guess: bool,
+    #[serde(default)]
+    pub quotas: [Option<usize>; 10],
+    #[serde(default)]
+    pub generator: Generator,
+    #[serde(default)]
+    pub undo: bool,
+    pub actions: Vec<Timed>,
+}
+
+pub fn apply(world: &mut WorldMap, history: &mut History, action: Action) {
+    match action {
+        Action::Open { x, y } => history.record(world, |w| {
+            w.open_tile(x, y);
+        }),
+        Action::Chord { x, y } => history.record(world, |w| w.chord_tile(x, y)),
+        Action::Flag { x, y, value } => history.record(world, |w| w.set_flag(x, y, value)),
+        Action::Equip { slot } => history.record(world, |w| {
+            w.equip(slot);
+        }),
+        Action::Use { item, x, y } => history.record(world, |w| {
+            w.use_item(item, x, y);
+        }),
+        Action::Perk { perk } => history.record(world, |w| {
+            w.choose_perk(perk);
+        }),
+        Action::Undo => {
+            history.undo(world);
+        }
+        Action::Restart => {}
+    }
+}
+
+impl Replay {
+    pub fn new(world: &WorldMap) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            mapw: world.mapw,
+            maph: world.maph,
+            mines: world.entity_store.len() - 2,
+            seed: world.seed,
+            no_guess: world.no_guess,
+            quotas: world.quota_overrides,
+            generator: world.generator,
+            undo: world.undo,
+            actions: vec![],
+        }
+    }
+
+    pub fn record(&mut self, t: f64, action: Action) {
+        self.actions.push(Timed { t, action });
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.actions.last().map_or(0., |a| a.t)
+    }
+
+    // a fresh copy of the board as it was dealt
+    pub fn deal(&self) -> Result<WorldMap, GenError> {
+        let mut world = WorldMap::generate_with(
+            self.mapw,
+            self.maph,
+            self.mines,
+            self.quotas,
+            self.generator,
+            self.seed,
+        )?;
+        world.no_guess = self.no_guess;
+        world.undo = self.undo;
+        Ok(world)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), SaveError> {
+        write_atomic(path, &serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> Result<Self, SaveError> {
+        let replay: Replay = serde_json::from_slice(&fs::read(path)?)?;
+        if replay.version != REPLAY_VERSION {
+            return Err(SaveError::Version(replay.version));
+        }
+        Ok(replay)
+    }
+}
+
+pub fn replay_dir() -> PathBuf {
+    data_dir().join("replays")
+}
+
+// New file name for a finished game, unique enough for one player.
+pub fn new_replay_path(replay: &Replay) -> PathBuf {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    replay_dir().join(format!("{stamp}-{}.json", replay.seed))
+}
+
+// Most recent replays first.
+pub fn list_replays() -> Vec<PathBuf> {
+    let Ok(dir) = fs::read_dir(replay_dir()) else {
+        return vec![];
+    };
+    let mut paths: Vec<PathBuf> = dir
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "json"))
+        .collect();
+    paths.sort();
+    paths.reverse();
+    paths
+}
+
+// Plays a replay back onto a board dealt from its seed.
+pub struct Player {
+    pub replay: Replay,
+    pub next: usize,
+    pub clock: f64,
+    pub speed: f64,
+    pub paused: bool,
+    history: History,
+}
+
+impl Player {
+    pub fn new(replay: Replay) -> Result<(Self, WorldMap), GenError> {
+        let world = replay.deal()?;
+        let player = Self {
+            replay,
+            next: 0,
+            clock: 0.,
+            speed: 1.,
+            paused: false,
+            history: History::new(),
+        };
+        Ok((player, world))
+    }
+
+    pub fn done(&self) -> bool {
+        self.next >= self.replay.actions.len()
+    }
+
+    // run the clock forward, applying whatever came due
+    pub fn advance(&mut self, world: &mut WorldMap, dt: f64) {
+        if self.paused || self.done() {
+            return;
+        }
+        self.clock += dt * self.speed;
+        while !self.done() && self.replay.actions[self.next].t <= self.clock {
+            apply(
+                world,
+                &mut self.history,
+                self.replay.actions[self.next].action,
+            );
+            self.next += 1;
+        }
+    }
+
+    // apply just the next action and stop there
+    pub fn step(&mut self, world: &mut WorldMap) {
+        self.paused = true;
+        if let Some(a) = self.replay.actions.get(self.next) {
+            apply(world, &mut self.history, a.action);
+            self.clock = a.t;
+            self.next += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::Solver;
+
+    // Plays a board a while, opening what the solver says is safe and
+    // fighting what it has pinned down, with a flag and an undo mixed in.
+    fn play(seed: u64) -> (WorldMap, Replay) {
+        let mut world = WorldMap::generate(30, 16, 120, seed).expect("default board deals");
+        world.no_guess = true;
+        world.undo = true;
+        let mut replay = Replay::new(&world);
+        let mut history = History::new();
+        let mut act = |world: &mut WorldMap, action| {
+            apply(world, &mut history, action);
+            replay.record(replay.actions.len() as f64, action);
+        };
+
+        act(&mut world, Action::Open { x: 15, y: 8 });
+        for step in 0..40 {
+            if world.game_over != 0 {
+                break;
+            }
+            let mut solver = Solver::new(&world);
+            solver.solve();
+            let closed = (0..world.maph)
+                .flat_map(|y| (0..world.mapw).map(move |x| (x, y)))
+                .filter(|&(x, y)| !world.open[(x, y)]);
+            let Some((x, y)) = closed
+                .clone()
+                .find(|&(x, y)| solver.known(x, y) == Some(0))
+                .or_else(|| closed.clone().find(|&(x, y)| solver.known(x, y).is_some()))
+            else {
+                break;
+            };
+            match step {
+                5 => act(&mut world, Action::Flag { x, y, value: 1 }),
+                9 => act(&mut world, Action::Undo),
+                _ => act(&mut world, Action::Open { x, y }),
+            }
+        }
+        (world, replay)
+    }
+
+    #[test]
+    fn replays_land_on_the_same_board() {
+        for seed in 1..=3 {
+            let (live, replay) = play(seed);
+            assert!(replay.actions.len() > 5, "seed {seed}");
+
+            let (mut player, mut world) = Player::new(replay).unwrap();
+            player.advance(&mut world, f64::MAX);
+            assert!(player.done());
+            assert_eq!(
+                serde_json::to_value(&world).unwrap(),
+                serde_json::to_value(&live).unwrap(),
+                "seed {seed}"
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let (_, replay) = play(2);
+        let back: Replay = serde_json::from_slice(&serde_json::to_vec(&replay).unwrap()).unwrap();
+        assert_eq!(back.actions, replay.actions);
+        assert_eq!(back.seed, replay.seed);
+    }
+}
@@ -0,0 +1,49 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DEFAULT_INC: u64 = 1442695040888963407;
+const MULTIPLIER: u64 = 6364136223846793005;
+
+// Small PCG generator so the rules don't need macroquad's global rand.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Self { state: 0 };
+        rng.rand();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.rand();
+        rng
+    }
+
+    pub fn rand(&mut self) -> u32 {
+        let oldstate = self.state;
+        self.state = oldstate.wrapping_mul(MULTIPLIER).wrapping_add(DEFAULT_INC);
+        let xorshifted = (((oldstate >> 18) ^ oldstate) >> 27) as u32;
+        let rot = (oldstate >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    // uniform in [low, high)
+    pub fn gen_range(&mut self, low: usize, high: usize) -> usize {
+        let r = self.rand() as f64 / (u32::MAX as f64 + 1.0);
+        (low as f64 + (high as f64 - low as f64) * r) as usize
+    }
+
+    pub fn shuffle<T>(&mut self, data: &mut [T]) {
+        for i in 1..data.len() {
+            let j = self.gen_range(0, i + 1);
+            data.swap(i, j);
+        }
+    }
+}
+
+// fresh game seed when the player didn't ask for one
+pub fn time_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
@@ -0,0 +1,746 @@
+// ██████╗ ██╗   ██╗██╗     ███████╗███████╗
+// ██╔══██╗██║   ██║██║     ██╔════╝██╔════╝
+// ██████╔╝██║   ██║██║     █████╗  ███████╗
+// ██╔══██╗██║   ██║██║     ██╔══╝  ╚════██║
+// ██║  ██║╚██████╔╝███████╗███████╗███████║
+// ╚═╝  ╚═╝ ╚═════╝ ╚══════╝╚══════╝╚══════╝
+//
+// Monsters, weapons and where things spawn, read from assets/rules.json.
+//
+// The file lists the nine monsters in breed order (bat first) and the nine
+// weapons in item order (unarmed first). A monster's loot is either "heal" or
+// the name of the weapon it drops. A monster's share means one in every `share` monsters is that breed,
+// rounded up. Bats have no share, they fill whatever is left and so must be
+// allowed on every terrain.
+//
+// Consumables are optional. Each one drops off the listed monsters with
+// `drop_chance` and turns up with `find_chance` when an empty tile of the
+// listed terrains opens. `power` is HP for a potion, the square's reach for a
+// scroll or fog-lift (1 is 3x3) and fights for a ward.
+//
+// A weapon with a `durability` loses 1 of it per fight, `ineffective_wear`
+// (2 unless set) on a bad matchup, and breaks at 0. Unarmed never breaks.
+//
+// A weapon hits for its `damage` (9 unless set), double with no damage back
+// on a good matchup and (damage+1)/2 with double damage back on a bad one.
+// Monsters that live through it stay up with the rest of their `hp` and can
+// be fought again. Left unset, good and neutral matchups drop anything in one
+// blow and bad ones anything up to level 5.
+//
+// Kills give the hero XP equal to the monster's level. The hero's `xp_curve`
+// is the total XP for level 2, 3 and so on.
+
+use std::{fmt, fs, io, path::Path, sync::OnceLock};
+
+use serde::Deserialize;
+
+use crate::{
+    entities::{Breed, Consumable, Entity, Weapon},
+    terrain::{self, Terrain},
+};
+
+// columns in chars.png
+const SPRITES: usize = 10;
+// flags and aura digits only go this high
+const MAX_LEVEL: i16 = 9;
+// weapons the hotbar has room to draw
+const MAX_SLOTS: usize = 6;
+
+static BUILTIN: &str = include_str!("../assets/rules.json");
+static RULES: OnceLock<Rules> = OnceLock::new();
+
+#[derive(Debug)]
+pub enum RulesError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RulesError::Io(e) => write!(f, "can't read rules: {e}"),
+            RulesError::Parse(e) => write!(f, "rules don't parse: {e}"),
+            RulesError::Invalid(problems) => {
+                write!(f, "rules have {} problem(s):", problems.len())?;
+                for p in problems {
+                    write!(f, "\n  {p}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for RulesError {}
+
+impl From<io::Error> for RulesError {
+    fn from(e: io::Error) -> Self {
+        RulesError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for RulesError {
+    fn from(e: serde_json::Error) -> Self {
+        RulesError::Parse(e)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HeroDef {
+    hp: i16,
+    sprite: usize,
+    // weapons carried at once, unarmed included
+    #[serde(default = "default_slots")]
+    slots: usize,
+    // total XP for each level past the first
+    #[serde(default)]
+    xp_curve: Vec<u32>,
+}
+
+fn default_slots() -> usize {
+    3
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MonsterDef {
+    name: String,
+    level: i16,
+    hp: i16,
+    sprite: usize,
+    terrains: Vec<String>,
+    #[serde(default)]
+    share: Option<f32>,
+    loot: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WeaponDef {
+    name: String,
+    sprite: usize,
+    effective: Vec<String>,
+    ineffective: Vec<String>,
+    #[serde(default = "default_damage")]
+    damage: i16,
+    #[serde(default)]
+    durability: Option<i16>,
+    #[serde(default = "default_ineffective_wear")]
+    ineffective_wear: i16,
+}
+
+// the top monster level, what a neutral blow takes off
+fn default_damage() -> i16 {
+    MAX_LEVEL
+}
+
+fn default_ineffective_wear() -> i16 {
+    2
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConsumableDef {
+    power: i16,
+    #[serde(default)]
+    dropped_by: Vec<String>,
+    #[serde(default)]
+    drop_chance: f32,
+    #[serde(default)]
+    found_on: Vec<String>,
+    #[serde(default)]
+    find_chance: f32,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConsumablesDef {
+    potion: Option<ConsumableDef>,
+    scroll: Option<ConsumableDef>,
+    fog: Option<ConsumableDef>,
+    ward: Option<ConsumableDef>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesDef {
+    hero: HeroDef,
+    monsters: Vec<MonsterDef>,
+    weapons: Vec<WeaponDef>,
+    #[serde(default)]
+    consumables: ConsumablesDef,
+}
+
+// Where a consumable comes from and how strong it is.
+#[derive(Clone, Debug, Default)]
+pub struct ConsumableRule {
+    pub power: i16,
+    pub dropped_by: Vec<Breed>,
+    pub drop_chance: f32,
+    pub found_on: [bool; terrain::COUNT],
+    pub find_chance: f32,
+}
+
+// What picking over a dead monster gets you.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Loot {
+    Nothing,
+    // heals 2, or raises max HP by 1 when already full
+    Heal,
+    Weapon(Weapon),
+}
+
+// How a weapon fares against a breed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Matchup {
+    // hits double and takes nothing back
+    Effective,
+    // takes the monster's level in damage
+    Neutral,
+    // hits half and takes double
+    Ineffective,
+}
+
+// Tables are indexed through Breed and Weapon, see the accessors below.
+#[derive(Clone, Debug)]
+pub struct Rules {
+    monsters: [Entity; Breed::COUNT],
+    monster_names: [String; Breed::COUNT],
+    monster_sprites: [usize; Breed::COUNT],
+    slots: usize,
+    xp_curve: Vec<u32>,
+    loot: [Loot; Breed::COUNT],
+    pub spawn_allowed: [[bool; terrain::COUNT]; Breed::COUNT],
+    pub spawn_dist: [f32; 9],
+    weapon_names: [String; Weapon::COUNT],
+    weapon_sprites: [usize; Weapon::COUNT],
+    effective: [Vec<Breed>; Weapon::COUNT],
+    ineffective: [Vec<Breed>; Weapon::COUNT],
+    damage: [i16; Weapon::COUNT],
+    durability: [Option<i16>; Weapon::COUNT],
+    ineffective_wear: [i16; Weapon::COUNT],
+    consumables: [ConsumableRule; Consumable::COUNT],
+}
+
+impl Rules {
+    pub fn monster(&self, breed: Breed) -> &Entity {
+        &self.monsters[breed.index()]
+    }
+
+    pub fn monster_name(&self, breed: Breed) -> &str {
+        &self.monster_names[breed.index()]
+    }
+
+    pub fn monster_sprite(&self, breed: Breed) -> usize {
+        self.monster_sprites[breed.index()]
+    }
+
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    // total XP that takes the hero past this level, None at the top
+    pub fn xp_for(&self, level: u16) -> Option<u32> {
+        let i = usize::from(level.max(1)) - 1;
+        self.xp_curve.get(i).copied()
+    }
+
+    pub fn loot(&self, breed: Breed) -> Loot {
+        self.loot[breed.index()]
+    }
+
+    pub fn weapon_name(&self, weapon: Weapon) -> &str {
+        &self.weapon_names[weapon.index()]
+    }
+
+    pub fn weapon_sprite(&self, weapon: Weapon) -> usize {
+        self.weapon_sprites[weapon.index()]
+    }
+
+    // breeds this weapon hits for 2x its damage, taking nothing back
+    pub fn effective(&self, weapon: Weapon) -> &[Breed] {
+        &self.effective[weapon.index()]
+    }
+
+    // breeds this weapon hits for (damage+1)/2, taking double back
+    pub fn ineffective(&self, weapon: Weapon) -> &[Breed] {
+        &self.ineffective[weapon.index()]
+    }
+
+    // HP one fight takes off the monster: 2x damage on an effective matchup,
+    // 1x on a neutral one and (damage+1)/2 on an ineffective one
+    pub fn blow(&self, weapon: Weapon, matchup: Matchup) -> i16 {
+        let damage = self.damage[weapon.index()];
+        match matchup {
+            Matchup::Effective => 2 * damage,
+            Matchup::Neutral => damage,
+            Matchup::Ineffective => (damage + 1) / 2,
+        }
+    }
+
+    // fights a fresh one lasts, None if it never breaks
+    pub fn durability(&self, weapon: Weapon) -> Option<i16> {
+        self.durability[weapon.index()]
+    }
+
+    // durability one fight costs
+    pub fn wear(&self, weapon: Weapon, matchup: Matchup) -> i16 {
+        match matchup {
+            Matchup::Ineffective => self.ineffective_wear[weapon.index()],
+            _ => 1,
+        }
+    }
+
+    pub fn consumable(&self, item: Consumable) -> &ConsumableRule {
+        &self.consumables[item.index()]
+    }
+
+    pub fn matchup(&self, weapon: Weapon, breed: Breed) -> Matchup {
+        if self.ineffective(weapon).contains(&breed) {
+            Matchup::Ineffective
+        } else if self.effective(weapon).contains(&breed) {
+            Matchup::Effective
+        } else {
+            Matchup::Neutral
+        }
+    }
+}
+
+// Rules in play. Whatever main installed, otherwise the ones built in.
+pub fn rules() -> &'static Rules {
+    RULES.get_or_init(|| match parse(BUILTIN) {
+        Ok(rules) => rules,
+        Err(e) => panic!("built in {e}"),
+    })
+}
+
+// Only works before anything has looked at the rules.
+pub fn install(rules: Rules) -> bool {
+    RULES.set(rules).is_ok()
+}
+
+pub fn load(path: &Path) -> Result<Rules, RulesError> {
+    parse(&fs::read_to_string(path)?)
+}
+
+pub fn parse(text: &str) -> Result<Rules, RulesError> {
+    let def: RulesDef = serde_json::from_str(text)?;
+    let mut problems = vec![];
+
+    let mut rules = Rules {
+        monsters: [crate::entities::NONE; Breed::COUNT],
+        monster_names: Default::default(),
+        monster_sprites: [0; Breed::COUNT],
+        slots: def.hero.slots,
+        xp_curve: def.hero.xp_curve.clone(),
+        loot: [Loot::Nothing; Breed::COUNT],
+        spawn_allowed: [[false; terrain::COUNT]; Breed::COUNT],
+        spawn_dist: [0.; 9],
+        weapon_names: Default::default(),
+        weapon_sprites: [0; Weapon::COUNT],
+        effective: Default::default(),
+        ineffective: Default::default(),
+        damage: [0; Weapon::COUNT],
+        durability: [None; Weapon::COUNT],
+        ineffective_wear: [0; Weapon::COUNT],
+        consumables: Default::default(),
+    };
+
+    if def.hero.hp < 1 {
+        problems.push(format!("hero: hp {} has to be at least 1", def.hero.hp));
+    }
+    if def.hero.sprite >= SPRITES {
+        problems.push(format!(
+            "hero: sprite {} is past the {SPRITES} in chars.png",
+            def.hero.sprite
+        ));
+    }
+    if !(1..=MAX_SLOTS).contains(&def.hero.slots) {
+        problems.push(format!(
+            "hero: slots {} isn't in 1..={MAX_SLOTS}",
+            def.hero.slots
+        ));
+    }
+    if def.hero.xp_curve.windows(2).any(|w| w[0] >= w[1]) || def.hero.xp_curve.first() == Some(&0) {
+        problems.push("hero: xp_curve has to climb from above 0".to_string());
+    }
+    rules.monsters[0] = Entity {
+        proto: None,
+        breed: Some(Breed::HERO),
+        level: 0,
+        hp: def.hero.hp,
+        active: false,
+    };
+    rules.monster_names[0] = "hero".to_string();
+    rules.monster_sprites[0] = def.hero.sprite;
+
+    if def.monsters.len() != 9 {
+        problems.push(format!("expected 9 monsters, found {}", def.monsters.len()));
+    }
+    for ((i, m), b) in def.monsters.iter().enumerate().zip(Breed::monsters()) {
+        let breed = b.index();
+        let who = format!("monster {breed} ({})", m.name);
+
+        if m.name.trim().is_empty() {
+            problems.push(format!("monster {breed}: needs a name"));
+        }
+        if def.monsters[..i].iter().any(|o| o.name == m.name) {
+            problems.push(format!("{who}: name is used twice"));
+        }
+        if !(1..=MAX_LEVEL).contains(&m.level) {
+            problems.push(format!("{who}: level {} isn't in 1..={MAX_LEVEL}", m.level));
+        }
+        if m.hp < 1 {
+            problems.push(format!("{who}: hp {} has to be at least 1", m.hp));
+        }
+        if m.sprite >= SPRITES {
+            problems.push(format!(
+                "{who}: sprite {} is past the {SPRITES} in chars.png",
+                m.sprite
+            ));
+        }
+
+        for t in &m.terrains {
+            match Terrain::from_name(t) {
+                Some(t) => rules.spawn_allowed[breed][t.index()] = true,
+                None => problems.push(format!(
+                    "{who}: unknown terrain \"{t}\", pick from {}",
+                    Terrain::ALL.map(Terrain::name).join(", ")
+                )),
+            }
+        }
+        if breed == 1 && rules.spawn_allowed[1].contains(&false) {
+            problems.push(format!(
+                "{who}: fills leftover tiles, so it has to allow every terrain"
+            ));
+        } else if m.terrains.is_empty() {
+            problems.push(format!("{who}: can't spawn anywhere"));
+        }
+
+        match (breed, m.share) {
+            (1, Some(_)) => problems.push(format!("{who}: fills leftover tiles, drop its share")),
+            (1, None) => {}
+            (_, Some(share)) if share >= 1. => rules.spawn_dist[i] = share,
+            (_, Some(share)) => problems.push(format!("{who}: share {share} has to be at least 1")),
+            (_, None) => problems.push(format!("{who}: needs a share")),
+        }
+
+        rules.monsters[breed] = Entity {
+            proto: None,
+            breed: Some(b),
+            level: m.level,
+            hp: m.hp,
+            active: false,
+        };
+        rules.monster_names[breed] = m.name.clone();
+        rules.monster_sprites[breed] = m.sprite;
+    }
+
+    let breed_of = |name: &str| {
+        Breed::monsters()
+            .zip(&def.monsters)
+            .find(|(_, m)| m.name == name)
+            .map(|(b, _)| b)
+    };
+    let weapon_of = |name: &str| {
+        Weapon::all()
+            .zip(&def.weapons)
+            .find(|(_, w)| w.name == name)
+            .map(|(w, _)| w)
+    };
+
+    for (m, b) in def.monsters.iter().zip(Breed::monsters()) {
+        rules.loot[b.index()] = match (m.loot.as_str(), weapon_of(&m.loot)) {
+            ("heal", _) => Loot::Heal,
+            (_, Some(w)) => Loot::Weapon(w),
+            (name, None) => {
+                problems.push(format!(
+                    "monster {} ({}): loot \"{name}\" is neither \"heal\" nor a weapon",
+                    b.index(),
+                    m.name
+                ));
+                Loot::Nothing
+            }
+        };
+    }
+
+    rules.weapon_names[0] = "none".to_string();
+    if def.weapons.len() != 9 {
+        problems.push(format!("expected 9 weapons, found {}", def.weapons.len()));
+    }
+    for (w, weapon) in def.weapons.iter().zip(Weapon::all()) {
+        let item = weapon.index();
+        let what = format!("weapon {item} ({})", w.name);
+
+        if w.name.trim().is_empty() {
+            problems.push(format!("weapon {item}: needs a name"));
+        }
+        if w.sprite >= SPRITES {
+            problems.push(format!(
+                "{what}: sprite {} is past the {SPRITES} in chars.png",
+                w.sprite
+            ));
+        }
+
+        for (list, out) in [
+            (&w.effective, &mut rules.effective[item]),
+            (&w.ineffective, &mut rules.ineffective[item]),
+        ] {
+            for name in list {
+                match breed_of(name) {
+                    Some(b) => out.push(b),
+                    None => problems.push(format!("{what}: no monster called \"{name}\"")),
+                }
+            }
+        }
+        for b in &rules.effective[item] {
+            if rules.ineffective[item].contains(b) {
+                problems.push(format!(
+                    "{what}: {} is both effective and ineffective",
+                    rules.monster_name(*b)
+                ));
+            }
+        }
+
+        match w.durability {
+            Some(_) if weapon == Weapon::UNARMED => problems.push(format!(
+                "{what}: is what's left when others break, drop its durability"
+            )),
+            Some(d) if d < 1 => {
+                problems.push(format!("{what}: durability {d} has to be at least 1"))
+            }
+            _ => {}
+        }
+        if w.damage < 1 {
+            problems.push(format!("{what}: damage {} has to be at least 1", w.damage));
+        }
+        if w.ineffective_wear < 1 {
+            problems.push(format!(
+                "{what}: ineffective_wear {} has to be at least 1",
+                w.ineffective_wear
+            ));
+        }
+
+        rules.weapon_names[item] = w.name.clone();
+        rules.weapon_sprites[item] = w.sprite;
+        rules.damage[item] = w.damage;
+        rules.durability[item] = w.durability;
+        rules.ineffective_wear[item] = w.ineffective_wear;
+    }
+
+    let c = &def.consumables;
+    for (item, d) in Consumable::ALL
+        .into_iter()
+        .zip([&c.potion, &c.scroll, &c.fog, &c.ward])
+    {
+        let Some(d) = d else {
+            continue;
+        };
+        let what = format!("consumable {}", item.name());
+        let out = &mut rules.consumables[item.index()];
+
+        if d.power < 1 {
+            problems.push(format!("{what}: power {} has to be at least 1", d.power));
+        }
+        for (name, chance) in [
+            ("drop_chance", d.drop_chance),
+            ("find_chance", d.find_chance),
+        ] {
+            if !(0. ..=1.).contains(&chance) {
+                problems.push(format!("{what}: {name} {chance} isn't in 0..=1"));
+            }
+        }
+        for name in &d.dropped_by {
+            match breed_of(name) {
+                Some(b) => out.dropped_by.push(b),
+                None => problems.push(format!("{what}: no monster called \"{name}\"")),
+            }
+        }
+        for t in &d.found_on {
+            match Terrain::from_name(t) {
+                Some(t) => out.found_on[t.index()] = true,
+                None => problems.push(format!("{what}: unknown terrain \"{t}\"")),
+            }
+        }
+        out.power = d.power;
+        out.drop_chance = d.drop_chance;
+        out.find_chance = d.find_chance;
+    }
+
+    if problems.is_empty() {
+        Ok(rules)
+    } else {
+        Err(RulesError::Invalid(problems))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    type Bend = fn(&mut Value);
+
+    // the built in rules with one thing bent, and whatever parse says to it
+    fn problems(bend: Bend) -> Vec<String> {
+        let mut def: Value = serde_json::from_str(BUILTIN).unwrap();
+        bend(&mut def);
+        match parse(&def.to_string()) {
+            Err(RulesError::Invalid(problems)) => problems,
+            Err(e) => panic!("expected validation problems, got {e}"),
+            Ok(_) => vec![],
+        }
+    }
+
+    #[test]
+    fn accepts_the_built_in_rules() {
+        assert!(parse(BUILTIN).is_ok());
+    }
+
+    #[test]
+    fn rejects_each_broken_rule() {
+        let cases: [(Bend, &str); 20] = [
+            (|d| d["hero"]["hp"] = json!(0), "hero: hp 0"),
+            (|d| d["hero"]["sprite"] = json!(SPRITES), "hero: sprite"),
+            (|d| d["hero"]["slots"] = json!(0), "hero: slots 0"),
+            (|d| d["hero"]["xp_curve"] = json!([5, 5]), "xp_curve"),
+            (
+                |d| {
+                    d["monsters"].as_array_mut().unwrap().pop();
+                },
+                "expected 9 monsters",
+            ),
+            (|d| d["monsters"][2]["name"] = json!(" "), "needs a name"),
+            (|d| d["monsters"][2]["name"] = json!("boney"), "used twice"),
+            (
+                |d| d["monsters"][2]["level"] = json!(0),
+                "(saurian): level 0",
+            ),
+            (|d| d["monsters"][2]["hp"] = json!(0), "(saurian): hp 0"),
+            (
+                |d| d["monsters"][2]["sprite"] = json!(SPRITES),
+                "(saurian): sprite",
+            ),
+            (
+                |d| d["monsters"][2]["terrains"] = json!(["moon"]),
+                "unknown terrain",
+            ),
+            (
+                |d| d["monsters"][0]["terrains"] = json!(["plain"]),
+                "every terrain",
+            ),
+            (|d| d["monsters"][0]["share"] = json!(3.0), "drop its share"),
+            (|d| d["monsters"][2]["share"] = json!(0.5), "share 0.5"),
+            (
+                |d| d["monsters"][2]["loot"] = json!("spoon"),
+                "loot \"spoon\"",
+            ),
+            (
+                |d| {
+                    d["weapons"].as_array_mut().unwrap().pop();
+                },
+                "expected 9 weapons",
+            ),
+            (
+                |d| d["weapons"][1]["effective"] = json!(["ghost"]),
+                "no monster",
+            ),
+            (
+                |d| d["weapons"][1]["ineffective"] = json!(["bat"]),
+                "both effective and ineffective",
+            ),
+            (
+                |d| d["weapons"][0]["durability"] = json!(5),
+                "drop its durability",
+            ),
+            (|d| d["weapons"][1]["durability"] = json!(0), "durability 0"),
+        ];
+        for (bend, expect) in cases {
+            let problems = problems(bend);
+            assert!(
+                problems.iter().any(|p| p.contains(expect)),
+                "wanted \"{expect}\" in {problems:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_broken_weapon_and_consumable_numbers() {
+        let cases: [(Bend, &str); 5] = [
+            (|d| d["weapons"][1]["damage"] = json!(0), "damage 0"),
+            (
+                |d| d["weapons"][1]["ineffective_wear"] = json!(0),
+                "ineffective_wear 0",
+            ),
+            (
+                |d| d["consumables"]["potion"]["power"] = json!(0),
+                "power 0",
+            ),
+            (
+                |d| d["consumables"]["potion"]["drop_chance"] = json!(2.0),
+                "drop_chance 2",
+            ),
+            (
+                |d| d["consumables"]["fog"]["found_on"] = json!(["moon"]),
+                "unknown terrain",
+            ),
+        ];
+        for (bend, expect) in cases {
+            let problems = problems(bend);
+            assert!(
+                problems.iter().any(|p| p.contains(expect)),
+                "wanted \"{expect}\" in {problems:?}"
+            );
+        }
+    }
+
+    // blows a fresh monster of the breed takes to drop
+    fn blows(weapon: &str, breed: &str) -> i16 {
+        let r = rules();
+        let weapon = Weapon::all().find(|&w| r.weapon_name(w) == weapon).unwrap();
+        let breed = Breed::monsters()
+            .find(|&b| r.monster_name(b) == breed)
+            .unwrap();
+        let hp = r.monster(breed).hp;
+        let blow = r.blow(weapon, r.matchup(weapon, breed));
+        (hp + blow - 1) / blow
+    }
+
+    #[test]
+    fn usual_matchups_kill_in_one_blow() {
+        // neutral
+        assert_eq!(blows("unarmed", "bat"), 1);
+        assert_eq!(blows("acid", "dragon"), 1);
+        // effective
+        assert_eq!(blows("sword", "boney"), 1);
+        assert_eq!(blows("cleaver", "dragon"), 1);
+        // ineffective, low levels still go down at once
+        assert_eq!(blows("unarmed", "saurian"), 1);
+        assert_eq!(blows("unarmed", "dweomer"), 1);
+        assert_eq!(blows("thirster", "vampire"), 1);
+        // ineffective past level 5 takes a second round
+        assert_eq!(blows("unarmed", "banshee"), 2);
+        assert_eq!(blows("sword", "dragon"), 2);
+        assert_eq!(blows("sunray", "goyle"), 2);
+
+        let r = rules();
+        for w in Weapon::all() {
+            for b in Breed::monsters() {
+                if r.matchup(w, b) != Matchup::Ineffective {
+                    assert_eq!(blows(r.weapon_name(w), r.monster_name(b)), 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reports_every_problem_at_once() {
+        let problems = problems(|d| {
+            d["hero"]["hp"] = json!(0);
+            d["monsters"][3]["level"] = json!(99);
+        });
+        assert_eq!(problems.len(), 2, "{problems:?}");
+    }
+}
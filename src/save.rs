@@ -0,0 +1,234 @@
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{grid::Grid, replay::Replay, worldmap::WorldMap};
+
+pub const SAVE_VERSION: u32 = 1;
+const SAVE_FILE: &str = "save.json";
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    Version(u32),
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "can't access save: {e}"),
+            SaveError::Format(e) => write!(f, "can't read save: {e}"),
+            SaveError::Version(v) => {
+                write!(f, "save is version {v}, this build reads {SAVE_VERSION}")
+            }
+            SaveError::Corrupt(what) => write!(f, "save is damaged: {what}"),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> Self {
+        SaveError::Format(e)
+    }
+}
+
+#[derive(Serialize)]
+struct SaveOut<'a> {
+    version: u32,
+    world: &'a WorldMap,
+    replay: Option<&'a Replay>,
+}
+
+#[derive(Deserialize)]
+struct SaveIn {
+    version: u32,
+    world: serde_json::Value,
+    // older saves don't carry the moves made so far
+    #[serde(default)]
+    replay: Option<Replay>,
+}
+
+// Where saves and other local files live. GLOAMWOOD_DATA wins, then the
+// platform's usual per-user data folder, then the working directory.
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("GLOAMWOOD_DATA") {
+        return PathBuf::from(dir);
+    }
+    if let Some(dir) = env::var_os("XDG_DATA_HOME") {
+        return PathBuf::from(dir).join("gloamwood");
+    }
+    if let Some(dir) = env::var_os("APPDATA") {
+        return PathBuf::from(dir).join("gloamwood");
+    }
+    if let Some(home) = env::var_os("HOME") {
+        return PathBuf::from(home).join(".local/share/gloamwood");
+    }
+    PathBuf::from(".")
+}
+
+pub fn save_path() -> PathBuf {
+    data_dir().join(SAVE_FILE)
+}
+
+// Writes through a temp file so a crash mid-save can't eat the old one.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(tmp, path)
+}
+
+pub fn save(world: &WorldMap, replay: Option<&Replay>, path: &Path) -> Result<(), SaveError> {
+    let out = SaveOut {
+        version: SAVE_VERSION,
+        world,
+        replay,
+    };
+    write_atomic(path, &serde_json::to_vec(&out)?)?;
+    Ok(())
+}
+
+pub fn load(path: &Path) -> Result<(WorldMap, Option<Replay>), SaveError> {
+    let saved: SaveIn = serde_json::from_slice(&fs::read(path)?)?;
+    if saved.version != SAVE_VERSION {
+        return Err(SaveError::Version(saved.version));
+    }
+
+    let mut world: WorldMap = serde_json::from_value(saved.world)?;
+    check(&world)?;
+    // saves from before the inventory only knew the weapon in hand
+    if world.inventory.is_empty() {
+        world.inventory.push(world.item);
+    }
+    world.recount();
+    world.restore_scratch();
+    Ok((world, saved.replay))
+}
+
+pub fn has_save() -> bool {
+    save_path().exists()
+}
+
+pub fn clear(path: &Path) {
+    let _ = fs::remove_file(path);
+}
+
+// Cheap sanity checks so a hand edited or truncated save can't index out
+// of bounds later.
+fn check(w: &WorldMap) -> Result<(), SaveError> {
+    fn sized<T>(layer: &Grid<T>, w: &WorldMap) -> bool {
+        layer.width() == w.mapw && layer.height() == w.maph
+    }
+
+    if !sized(&w.terrains, w)
+        || !sized(&w.entities, w)
+        || !sized(&w.auras, w)
+        || !sized(&w.open, w)
+        || !sized(&w.show_terrain, w)
+        || !sized(&w.flags, w)
+    {
+        return Err(SaveError::Corrupt("board layers don't match its size"));
+    }
+
+    if w.entity_store.len() < 2 || w.entities.iter().any(|&e| e >= w.entity_store.len()) {
+        return Err(SaveError::Corrupt("unknown entity on the board"));
+    }
+
+    if w.hero_pos.0 >= w.mapw || w.hero_pos.1 >= w.maph {
+        return Err(SaveError::Corrupt("hero is off the board"));
+    }
+
+    // entity 1 is the hero, it goes down with the first click
+    if w.initialized && w.entities[w.hero_pos] != 1 {
+        return Err(SaveError::Corrupt("hero isn't where the save says"));
+    }
+
+    let tiles = w.mapw * w.maph;
+    if w.gen_pool.iter().any(|&n| n >= tiles) {
+        return Err(SaveError::Corrupt("monster pool points off the board"));
+    }
+    if w.gen_i > w.gen_pool.len() {
+        return Err(SaveError::Corrupt("monster pool is cut short"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_save(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("gloamwood-{}-{name}.json", std::process::id()))
+    }
+
+    fn played() -> WorldMap {
+        let mut world = WorldMap::generate(30, 16, 120, 5).expect("default board deals");
+        world.open_tile(15, 8);
+        world
+    }
+
+    // writes the board with one field bent out of shape and loads it back
+    fn load_bent(name: &str, bend: impl FnOnce(&mut serde_json::Value)) -> Result<(), SaveError> {
+        let path = temp_save(name);
+        let mut world = serde_json::to_value(played()).unwrap();
+        bend(&mut world);
+        let out = serde_json::json!({ "version": SAVE_VERSION, "world": world });
+        write_atomic(&path, &serde_json::to_vec(&out).unwrap()).unwrap();
+        let loaded = load(&path);
+        clear(&path);
+        loaded.map(|_| ())
+    }
+
+    // recount rebuilds the auras and counts it was saved with
+    #[test]
+    fn round_trips_a_board_in_play() {
+        let world = played();
+        let path = temp_save("round-trip");
+        save(&world, None, &path).unwrap();
+        let (loaded, replay) = load(&path).unwrap();
+        clear(&path);
+
+        assert!(replay.is_none());
+        assert_eq!(
+            serde_json::to_value(&loaded).unwrap(),
+            serde_json::to_value(&world).unwrap()
+        );
+    }
+
+    #[test]
+    fn refuses_damaged_saves() {
+        let corrupt = |r| matches!(r, Err(SaveError::Corrupt(_)));
+
+        assert!(load_bent("intact", |_| {}).is_ok());
+        assert!(corrupt(load_bent("pool-off-board", |w| {
+            w["gen_pool"][0] = serde_json::json!(30 * 16);
+        })));
+        assert!(corrupt(load_bent("pool-cut-short", |w| {
+            w["gen_pool"].as_array_mut().unwrap().truncate(1);
+            w["gen_i"] = serde_json::json!(2);
+        })));
+        assert!(corrupt(load_bent("hero-moved", |w| {
+            let x = w["hero_pos"][0].as_u64().unwrap();
+            w["hero_pos"][0] = serde_json::json!((x + 1) % 30);
+        })));
+        assert!(corrupt(load_bent("layer-short", |w| {
+            w["open"].as_array_mut().unwrap().pop();
+        })));
+    }
+}
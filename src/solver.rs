@@ -0,0 +1,663 @@
+use std::collections::VecDeque;
+
+use crate::{entities::Breed, rng::Rng, rules::rules, worldmap::WorldMap};
+
+// Domains are bitmasks over breeds, bit 0 meaning "no monster".
+pub const ALL: u16 = (1 << 10) - 1;
+
+// Sums are tracked as u128 bitsets, anything past 127 is lumped into bit 127.
+const SUM_BITS: i16 = 128;
+
+const MAX_REPAIRS: usize = 400;
+const SAMPLE_TRIES: usize = 8;
+
+#[derive(Clone, Debug)]
+pub struct Constraint {
+    pub pos: (usize, usize),
+    pub tiles: Vec<usize>,
+    pub lo: i16,
+    pub hi: i16,
+}
+
+// Logical solver over what the player can actually see: open tiles, the
+// auras printed on them (clouds only tell you "some" or "none"), revealed
+// monsters, visible terrain and the per-breed counts in the HUD.
+#[derive(Clone)]
+pub struct Solver {
+    pub mapw: usize,
+    pub maph: usize,
+    pub domains: Vec<u16>,
+    pub constraints: Vec<Constraint>,
+    watch: Vec<Vec<usize>>,
+    counts: [i16; 10],
+    use_counts: bool,
+}
+
+#[inline(always)]
+pub fn level(breed: usize) -> i16 {
+    Breed::new(breed).map_or(0, |b| rules().monster(b).level)
+}
+
+#[inline(always)]
+pub fn single(domain: u16) -> Option<usize> {
+    if domain.count_ones() == 1 {
+        Some(domain.trailing_zeros() as usize)
+    } else {
+        None
+    }
+}
+
+pub fn breeds(domain: u16) -> impl Iterator<Item = usize> {
+    (0..10).filter(move |b| domain & (1 << b) != 0)
+}
+
+fn reach(domain: u16) -> u128 {
+    breeds(domain).fold(0, |acc, b| acc | 1 << level(b).min(SUM_BITS - 1))
+}
+
+fn shl_sat(bits: u128, s: i16) -> u128 {
+    if s == 0 {
+        return bits;
+    }
+    let over = bits >> (SUM_BITS - s) != 0;
+    (bits << s) | if over { 1 << (SUM_BITS - 1) } else { 0 }
+}
+
+// all sums a + b
+fn add(mut a: u128, b: u128) -> u128 {
+    let mut out = 0;
+    while a != 0 {
+        out |= shl_sat(b, a.trailing_zeros() as i16);
+        a &= a - 1;
+    }
+    out
+}
+
+fn window(lo: i16, hi: i16) -> u128 {
+    let lo = lo.max(0);
+    let hi = hi.min(SUM_BITS - 1);
+    if hi < lo {
+        return 0;
+    }
+    (u128::MAX >> (SUM_BITS - 1 - hi)) & (u128::MAX << lo)
+}
+
+impl Solver {
+    pub fn new(world: &WorldMap) -> Self {
+        let (mapw, maph) = (world.mapw, world.maph);
+        let mut domains = vec![ALL; mapw * maph];
+        let mut constraints = Vec::new();
+        let mut watch = vec![vec![]; mapw * maph];
+
+        for y in 0..maph {
+            for x in 0..mapw {
+                let i = y * mapw + x;
+                if world.open[(x, y)] {
+                    let ent = world.entity(x, y);
+                    // the dead are as good as empty until looted
+                    let alive = ent.breed.filter(|_| ent.hp > 0);
+                    domains[i] = 1 << alive.map_or(0, Breed::index);
+
+                    // aura is only printed on tiles without a monster
+                    if ent.level > 0 {
+                        continue;
+                    }
+
+                    let aura = world.auras[(x, y)];
+                    let (lo, hi) = if !world.terrains[(x, y)].props().hides_aura {
+                        (aura, aura)
+                    } else if aura > 0 {
+                        (1, i16::MAX)
+                    } else {
+                        (0, 0)
+                    };
+
+                    let tiles: Vec<usize> = world
+                        .open
+                        .neighbors(x, y)
+                        .map(|(xx, yy)| yy * mapw + xx)
+                        .collect();
+                    for &t in &tiles {
+                        watch[t].push(constraints.len());
+                    }
+                    constraints.push(Constraint {
+                        pos: (x, y),
+                        tiles,
+                        lo,
+                        hi,
+                    });
+                } else if world.show_terrain[(x, y)] {
+                    let t = world.terrains[(x, y)];
+                    domains[i] = Breed::monsters()
+                        .filter(|&b| t.allows(b))
+                        .fold(1, |acc, b| acc | 1 << b.index());
+                }
+            }
+        }
+
+        Self {
+            mapw,
+            maph,
+            domains,
+            constraints,
+            watch,
+            counts: world.counts,
+            use_counts: true,
+        }
+    }
+
+    // Same board knowledge, but only the given constraints (and optionally
+    // the HUD counts) get to take part. Used to explain deductions.
+    pub fn restricted(&self, keep: &[usize], use_counts: bool) -> Self {
+        let constraints: Vec<Constraint> =
+            keep.iter().map(|&c| self.constraints[c].clone()).collect();
+        let mut watch = vec![vec![]; self.domains.len()];
+        for (ci, c) in constraints.iter().enumerate() {
+            for &t in &c.tiles {
+                watch[t].push(ci);
+            }
+        }
+
+        Self {
+            mapw: self.mapw,
+            maph: self.maph,
+            domains: self.domains.clone(),
+            constraints,
+            watch,
+            counts: self.counts,
+            use_counts,
+        }
+    }
+
+    // Take the player's flags at their word, a flag being the level they
+    // think is on the tile.
+    pub fn assume_flags(&mut self, world: &WorldMap) {
+        for y in 0..self.maph {
+            for x in 0..self.mapw {
+                let flag = world.flags[(x, y)];
+                if flag == 0 || world.open[(x, y)] {
+                    continue;
+                }
+                let mask = (1..10)
+                    .filter(|&b| level(b) == flag)
+                    .fold(0, |acc, b| acc | 1 << b);
+                self.domains[y * self.mapw + x] &= mask;
+            }
+        }
+    }
+
+    // constraints that mention tile t
+    #[inline(always)]
+    pub fn watching(&self, t: usize) -> &[usize] {
+        &self.watch[t]
+    }
+
+    #[inline(always)]
+    pub fn counts(&self) -> &[i16; 10] {
+        &self.counts
+    }
+
+    #[inline(always)]
+    pub fn domain(&self, x: usize, y: usize) -> u16 {
+        self.domains[y * self.mapw + x]
+    }
+
+    // breed on the tile if it is pinned down, 0 meaning empty
+    #[inline(always)]
+    pub fn known(&self, x: usize, y: usize) -> Option<usize> {
+        single(self.domain(x, y))
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.domains.iter().all(|&d| single(d).is_some())
+    }
+
+    // Runs every deduction to a fixpoint. False means the visible state
+    // contradicts itself (which can't happen on a well formed board).
+    pub fn solve(&mut self) -> bool {
+        let mut domains = self.domains.clone();
+        let all: Vec<usize> = (0..self.constraints.len()).collect();
+        if self.propagate(&mut domains, all).is_err() {
+            return false;
+        }
+        self.domains = domains;
+
+        loop {
+            let mut domains = self.domains.clone();
+            let counted = if self.use_counts {
+                self.apply_counts(&mut domains)
+            } else {
+                Ok(false)
+            };
+            match counted {
+                Ok(true) => {
+                    let all: Vec<usize> = (0..self.constraints.len()).collect();
+                    if self.propagate(&mut domains, all).is_err() {
+                        return false;
+                    }
+                    self.domains = domains;
+                    continue;
+                }
+                Ok(false) => {}
+                Err(_) => return false,
+            }
+
+            match self.probe() {
+                Ok(true) => continue,
+                Ok(false) => return true,
+                Err(_) => return false,
+            }
+        }
+    }
+
+    // Narrow a constraint's tiles to the values some completion allows.
+    fn revise(&self, c: &Constraint, domains: &mut [u16]) -> Result<bool, ()> {
+        let n = c.tiles.len();
+        let mut prefix = vec![1u128; n + 1];
+        let mut suffix = vec![1u128; n + 1];
+        for i in 0..n {
+            prefix[i + 1] = add(prefix[i], reach(domains[c.tiles[i]]));
+            suffix[n - i - 1] = add(suffix[n - i], reach(domains[c.tiles[n - i - 1]]));
+        }
+
+        if prefix[n] & window(c.lo, c.hi) == 0 {
+            return Err(());
+        }
+
+        let mut changed = false;
+        for i in 0..n {
+            let t = c.tiles[i];
+            if single(domains[t]).is_some() {
+                continue;
+            }
+
+            let others = add(prefix[i], suffix[i + 1]);
+            for b in breeds(domains[t]) {
+                let l = level(b);
+                let lo = c.lo.saturating_sub(l);
+                let hi = c.hi.saturating_sub(l);
+                if others & window(lo, hi) == 0 {
+                    domains[t] &= !(1 << b);
+                    changed = true;
+                }
+            }
+        }
+
+        Ok(changed)
+    }
+
+    fn propagate(&self, domains: &mut [u16], start: Vec<usize>) -> Result<(), ()> {
+        let mut queued = vec![false; self.constraints.len()];
+        let mut queue = VecDeque::with_capacity(start.len());
+        for c in start {
+            if !queued[c] {
+                queued[c] = true;
+                queue.push_back(c);
+            }
+        }
+
+        while let Some(ci) = queue.pop_front() {
+            queued[ci] = false;
+            let c = &self.constraints[ci];
+            let before: Vec<u16> = c.tiles.iter().map(|&t| domains[t]).collect();
+            if !self.revise(c, domains)? {
+                continue;
+            }
+
+            for (k, &t) in c.tiles.iter().enumerate() {
+                if domains[t] == before[k] {
+                    continue;
+                }
+                if domains[t] == 0 {
+                    return Err(());
+                }
+                for &other in &self.watch[t] {
+                    if !queued[other] {
+                        queued[other] = true;
+                        queue.push_back(other);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // The HUD shows how many of each breed are left, so once they are all
+    // found nobody else can be that breed, and if only that many tiles could
+    // be it they all are.
+    fn apply_counts(&self, domains: &mut [u16]) -> Result<bool, ()> {
+        let mut changed = false;
+        for b in 1..10 {
+            let bit = 1 << b;
+            let fixed = domains.iter().filter(|&&d| d == bit).count() as i16;
+            let possible = domains.iter().filter(|&&d| d & bit != 0).count() as i16;
+            let count = self.counts[b];
+
+            if fixed > count || possible < count {
+                return Err(());
+            }
+
+            if fixed == count && possible > fixed {
+                for d in domains.iter_mut() {
+                    if *d != bit && *d & bit != 0 {
+                        *d &= !bit;
+                        changed = true;
+                    }
+                }
+            } else if possible == count && fixed < count {
+                for d in domains.iter_mut() {
+                    if *d & bit != 0 {
+                        *d = bit;
+                    }
+                }
+                changed = true;
+            }
+
+            if domains.contains(&0) {
+                return Err(());
+            }
+        }
+
+        Ok(changed)
+    }
+
+    // Try each value of each undecided frontier tile and throw out the ones
+    // that lead to a contradiction. This picks up the patterns that need
+    // several numbers at once.
+    fn probe(&mut self) -> Result<bool, ()> {
+        let mut changed = false;
+        for t in 0..self.domains.len() {
+            if self.watch[t].is_empty() || single(self.domains[t]).is_some() {
+                continue;
+            }
+
+            for b in breeds(self.domains[t]) {
+                let mut trial = self.domains.clone();
+                trial[t] = 1 << b;
+                if self.propagate(&mut trial, self.watch[t].clone()).is_err() {
+                    let mut domains = self.domains.clone();
+                    domains[t] &= !(1 << b);
+                    if domains[t] == 0 {
+                        return Err(());
+                    }
+                    self.propagate(&mut domains, self.watch[t].clone())?;
+                    self.domains = domains;
+                    changed = true;
+                }
+            }
+        }
+
+        Ok(changed)
+    }
+
+    // One random filling of the undecided frontier tiles that agrees with
+    // every number. Not exactly uniform, but close enough to estimate odds.
+    pub fn sample(&self, rng: &mut Rng) -> Option<Vec<u16>> {
+        let order: Vec<usize> = (0..self.domains.len())
+            .filter(|&t| !self.watch[t].is_empty() && single(self.domains[t]).is_none())
+            .collect();
+
+        for _ in 0..SAMPLE_TRIES {
+            let mut domains = self.domains.clone();
+            let mut ok = true;
+            for &t in &order {
+                if single(domains[t]).is_some() {
+                    continue;
+                }
+
+                let mut choices: Vec<usize> = breeds(domains[t]).collect();
+                rng.shuffle(&mut choices);
+                let picked = choices.into_iter().find_map(|b| {
+                    let mut trial = domains.clone();
+                    trial[t] = 1 << b;
+                    self.propagate(&mut trial, self.watch[t].clone())
+                        .ok()
+                        .map(|_| trial)
+                });
+
+                match picked {
+                    Some(d) => domains = d,
+                    None => {
+                        ok = false;
+                        break;
+                    }
+                }
+            }
+
+            if ok {
+                return Some(domains);
+            }
+        }
+
+        None
+    }
+}
+
+// Play the board as a perfect logician would: open every provably empty
+// tile, fight the provably placed monsters, and repeat until nothing new can
+// be learned. Returns the final board and what the solver knows about it,
+// or None if the visible state is contradictory.
+pub fn play_out(world: &WorldMap) -> Option<(WorldMap, Solver)> {
+    let mut sim = world.clone();
+    // fights only matter for what they reveal here
+    let (hx, hy) = sim.hero_pos;
+    let heroid = sim.entities[(hx, hy)];
+    sim.entity_store[heroid].hp = i16::MAX / 2;
+
+    let mut known: Option<Vec<u16>> = None;
+    loop {
+        let mut solver = Solver::new(&sim);
+        // anything already worked out about a closed tile still holds
+        if let Some(prev) = known {
+            for (i, d) in solver.domains.iter_mut().enumerate() {
+                if !sim.open.as_slice()[i] {
+                    *d &= prev[i];
+                }
+            }
+        }
+        if !solver.solve() {
+            return None;
+        }
+        known = Some(solver.domains.clone());
+
+        let mut safe = vec![];
+        let mut fights = vec![];
+        for y in 0..sim.maph {
+            for x in 0..sim.mapw {
+                if sim.open[(x, y)] {
+                    continue;
+                }
+                match solver.known(x, y) {
+                    Some(0) => safe.push((x, y)),
+                    // only worth fighting if it borders something unknown
+                    Some(_)
+                        if sim
+                            .open
+                            .neighbors(x, y)
+                            .any(|(xx, yy)| solver.known(xx, yy).is_none()) =>
+                    {
+                        fights.push((x, y))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if !safe.is_empty() {
+            for (x, y) in safe {
+                sim.open_tile(x, y);
+            }
+        } else if !fights.is_empty() {
+            for (x, y) in fights {
+                // fight until it drops, then loot
+                sim.open_tile(x, y);
+                while sim.entity(x, y).hp > 0 && sim.game_over == 0 {
+                    sim.open_tile(x, y);
+                }
+                sim.open_tile(x, y);
+            }
+        } else {
+            return Some((sim, solver));
+        }
+    }
+}
+
+pub fn is_solvable(world: &WorldMap) -> bool {
+    play_out(world).is_some_and(|(_, solver)| solver.is_solved())
+}
+
+// Shuffle monsters around until the board can be cleared from the first
+// click without guessing. Whenever the perfect player gets stuck, every
+// undecided monster next to one of the numbers it is stuck on is moved
+// somewhere out of sight, which makes that number give its neighbors away.
+//
+// Expects the first click to have been remined but not revealed yet.
+pub fn make_solvable(world: &mut WorldMap, x: usize, y: usize) -> bool {
+    let mut rng = Rng::new(world.seed ^ ((y * world.mapw + x) as u64 + 1).rotate_left(32));
+    let (mapw, maph) = (world.mapw, world.maph);
+
+    for _ in 0..MAX_REPAIRS {
+        let mut start = world.clone();
+        start.initialized = true;
+        start.open_tile(x, y);
+
+        let Some((sim, solver)) = play_out(&start) else {
+            return false;
+        };
+        if solver.is_solved() {
+            return true;
+        }
+
+        let undecided = |t: usize| single(solver.domains[t]).is_none();
+        let mut stuck: Vec<&Constraint> = solver
+            .constraints
+            .iter()
+            .filter(|c| c.tiles.iter().any(|&t| undecided(t)))
+            .collect();
+        rng.shuffle(&mut stuck);
+
+        // tiles nobody has seen the edge of yet
+        let mut hidden: Vec<usize> = (0..mapw * maph)
+            .filter(|&i| {
+                let (xx, yy) = (i % mapw, i / mapw);
+                world.entities[(xx, yy)] == 0
+                    && !sim.open.neighbors(xx, yy).any(|(a, b)| sim.open[(a, b)])
+                    && (xx.abs_diff(x) > 1 || yy.abs_diff(y) > 1)
+            })
+            .collect();
+        rng.shuffle(&mut hidden);
+
+        let Some(c) = stuck.into_iter().find(|c| {
+            c.tiles
+                .iter()
+                .any(|&t| undecided(t) && world.entities.as_slice()[t] > 1)
+        }) else {
+            return false;
+        };
+
+        for &t in &c.tiles {
+            let (xx, yy) = (t % mapw, t / mapw);
+            let eid = world.entities[(xx, yy)];
+            if !undecided(t) || eid < 2 {
+                continue;
+            }
+
+            let Some(breed) = world.entity(xx, yy).breed else {
+                continue;
+            };
+            let Some(k) = hidden
+                .iter()
+                .position(|&i| world.terrains.as_slice()[i].allows(breed))
+            else {
+                return false;
+            };
+            let dest = hidden.swap_remove(k);
+
+            world.set_monster(xx, yy, 0);
+            world.set_monster(dest % mapw, dest / mapw, eid);
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{entities::Weapon, rules::Matchup};
+
+    // the first click as WorldMap::first_click sets it up for the solver
+    fn dealt(seed: u64) -> (WorldMap, usize, usize) {
+        let mut world = WorldMap::generate(30, 16, 120, seed).expect("default board deals");
+        let (x, y) = (15, 8);
+        world.remine(x, y);
+        world.set_monster(x, y, 1);
+        world.hero_pos = (x, y);
+        (world, x, y)
+    }
+
+    #[test]
+    fn domains_read_back_their_breeds() {
+        assert_eq!(single(1 << 3), Some(3));
+        assert_eq!(single(1), Some(0));
+        assert_eq!(single(0b110), None);
+        assert_eq!(breeds(0b1010).collect::<Vec<_>>(), vec![1, 3]);
+    }
+
+    // whatever gets pinned down is what's really there
+    #[test]
+    fn deductions_match_the_board() {
+        for seed in 1..=5 {
+            let mut world = WorldMap::generate(30, 16, 120, seed).expect("default board deals");
+            world.open_tile(15, 8);
+            let mut solver = Solver::new(&world);
+            assert!(solver.solve(), "seed {seed}");
+
+            for y in 0..world.maph {
+                for x in 0..world.mapw {
+                    let ent = world.entity(x, y);
+                    let truth = ent.breed.filter(|_| ent.hp > 0).map_or(0, Breed::index);
+                    if let Some(known) = solver.known(x, y) {
+                        assert_eq!(known, truth, "seed {seed} at {x},{y}");
+                    }
+                }
+            }
+        }
+    }
+
+    // Monsters that outlast a blow get fought down and looted while the
+    // solver plays a board out, so no-guess repairs keep working.
+    #[test]
+    fn plays_out_monsters_that_take_several_blows() {
+        let unarmed = rules().blow(Weapon::UNARMED, Matchup::Ineffective);
+        for seed in 1..=10 {
+            let (mut world, x, y) = dealt(seed);
+            assert!(world.entity_store.iter().any(|e| e.hp > unarmed));
+            assert!(make_solvable(&mut world, x, y), "seed {seed}");
+
+            let mut start = world.clone();
+            start.initialized = true;
+            start.open_tile(x, y);
+            let (sim, _) = play_out(&start).expect("plays out");
+            for (p, &eid) in sim.entities.positions() {
+                if sim.open[p] && eid > 1 {
+                    assert_eq!(sim.entity_store[eid].hp, 0, "seed {seed} at {p:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn repaired_boards_clear_without_a_guess() {
+        for seed in 1..=3 {
+            let (mut world, x, y) = dealt(seed);
+            assert!(make_solvable(&mut world, x, y), "seed {seed}");
+
+            let mut start = world.clone();
+            start.initialized = true;
+            start.open_tile(x, y);
+            assert!(is_solvable(&start), "seed {seed}");
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::{collections::BTreeMap, fs, io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    mapgen::Generator,
+    save::{data_dir, write_atomic, SaveError},
+    worldmap::WorldMap,
+};
+
+pub const STATS_VERSION: u32 = 1;
+const STATS_FILE: &str = "stats.json";
+
+// Totals for one board size.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ModeStats {
+    pub wins: u32,
+    pub losses: u32,
+    // fastest win in seconds, runs that used undo are kept apart
+    pub best_time: Option<f64>,
+    pub best_assisted: Option<f64>,
+    pub max_hp: i16,
+    pub kills: [u32; 10],
+    pub weapons: [u32; 10],
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Stats {
+    pub version: u32,
+    pub modes: BTreeMap<String, ModeStats>,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            version: STATS_VERSION,
+            modes: BTreeMap::new(),
+        }
+    }
+}
+
+pub fn mode_key(mapw: usize, maph: usize, mines: usize) -> String {
+    format!("{mapw}x{maph}/{mines}")
+}
+
+impl Stats {
+    pub fn mode(&self, mapw: usize, maph: usize, mines: usize) -> Option<&ModeStats> {
+        self.modes.get(&mode_key(mapw, maph, mines))
+    }
+
+    // Adds a finished game to its board size. Boards with custom breed
+    // counts or other landscapes get their own entry.
+    pub fn record(&mut self, world: &WorldMap) {
+        let mines = world.entity_store.len() - 2;
+        let mut key = mode_key(world.mapw, world.maph, mines);
+        if world.quota_overrides.iter().any(Option::is_some) {
+            key += " custom";
+        }
+        if world.generator != Generator::Fissure {
+            key = format!("{key} {}", world.generator.name().to_lowercase());
+        }
+        let mode = self.modes.entry(key).or_default();
+
+        match world.game_over {
+            1 => {
+                mode.wins += 1;
+                let best = if world.undos > 0 {
+                    &mut mode.best_assisted
+                } else {
+                    &mut mode.best_time
+                };
+                if best.is_none_or(|b| world.play_time < b) {
+                    *best = Some(world.play_time);
+                }
+            }
+            2 => mode.losses += 1,
+            _ => return,
+        }
+
+        mode.max_hp = mode.max_hp.max(world.maxhp);
+        for (total, n) in mode.kills.iter_mut().zip(world.kills) {
+            *total += n;
+        }
+        for (total, n) in mode.weapons.iter_mut().zip(world.weapons_used) {
+            *total += n;
+        }
+    }
+}
+
+pub fn stats_path() -> PathBuf {
+    data_dir().join(STATS_FILE)
+}
+
+// No file yet just means no games yet.
+pub fn load() -> Result<Stats, SaveError> {
+    let bytes = match fs::read(stats_path()) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Stats::default()),
+        Err(e) => return Err(e.into()),
+    };
+    let stats: Stats = serde_json::from_slice(&bytes)?;
+    if stats.version != STATS_VERSION {
+        return Err(SaveError::Version(stats.version));
+    }
+    Ok(stats)
+}
+
+pub fn save(stats: &Stats) -> Result<(), SaveError> {
+    write_atomic(&stats_path(), &serde_json::to_vec_pretty(stats)?)?;
+    Ok(())
+}
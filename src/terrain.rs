@@ -0,0 +1,162 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{entities::Breed, rules::rules};
+
+// Terrain from lowest to highest. The order doubles as height, mapgen
+// quantizes into it and placement bends terrain to its nearest neighbor.
+// Saved as the plain number so older saves still load.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "i16", try_from = "i16")]
+pub enum Terrain {
+    #[default]
+    Deep,
+    Shallow,
+    Swamp,
+    Plain,
+    Forest,
+    DarkForest,
+    Hill,
+    Mountain,
+    Clouds,
+    Peak,
+    Lava,
+}
+
+pub const COUNT: usize = 11;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Animation {
+    Still,
+    // bobs up and down in a wave
+    Swell,
+    // slides side to side
+    Ripple,
+}
+
+pub struct Props {
+    // what the rules file calls it
+    pub name: &'static str,
+    // column in tiles.png
+    pub sprite: usize,
+    // shows under the fog from the start
+    pub reveals: bool,
+    // aura prints as ? instead of the sum
+    pub hides_aura: bool,
+    pub blocks_chord: bool,
+    pub animation: Animation,
+}
+
+const fn props(name: &'static str, sprite: usize) -> Props {
+    Props {
+        name,
+        sprite,
+        reveals: false,
+        hides_aura: false,
+        blocks_chord: false,
+        animation: Animation::Still,
+    }
+}
+
+static PROPS: [Props; COUNT] = [
+    Props {
+        animation: Animation::Swell,
+        ..props("deep", 0)
+    },
+    Props {
+        animation: Animation::Ripple,
+        ..props("shallow", 1)
+    },
+    props("swamp", 2),
+    props("plain", 3),
+    props("forest", 4),
+    props("darkforest", 5),
+    props("hill", 6),
+    props("mountain", 7),
+    Props {
+        hides_aura: true,
+        blocks_chord: true,
+        ..props("clouds", 8)
+    },
+    Props {
+        reveals: true,
+        ..props("peak", 9)
+    },
+    props("lava", 10),
+];
+
+impl Terrain {
+    pub const ALL: [Terrain; COUNT] = [
+        Terrain::Deep,
+        Terrain::Shallow,
+        Terrain::Swamp,
+        Terrain::Plain,
+        Terrain::Forest,
+        Terrain::DarkForest,
+        Terrain::Hill,
+        Terrain::Mountain,
+        Terrain::Clouds,
+        Terrain::Peak,
+        Terrain::Lava,
+    ];
+
+    #[inline(always)]
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn from_index(i: usize) -> Option<Self> {
+        Self::ALL.get(i).copied()
+    }
+
+    // quantized mapgen height, clamped into range
+    pub fn from_height(h: i16) -> Self {
+        Self::ALL[h.clamp(0, COUNT as i16 - 1) as usize]
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.props().name == name)
+    }
+
+    #[inline(always)]
+    pub fn props(self) -> &'static Props {
+        &PROPS[self.index()]
+    }
+
+    pub fn name(self) -> &'static str {
+        self.props().name
+    }
+
+    // spawn table from the rules file
+    #[inline(always)]
+    pub fn allows(self, breed: Breed) -> bool {
+        rules().spawn_allowed[breed.index()][self.index()]
+    }
+}
+
+impl From<Terrain> for i16 {
+    fn from(t: Terrain) -> Self {
+        t.index() as i16
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownTerrain(i16);
+
+impl fmt::Display for UnknownTerrain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown terrain {}", self.0)
+    }
+}
+
+impl TryFrom<i16> for Terrain {
+    type Error = UnknownTerrain;
+
+    fn try_from(t: i16) -> Result<Self, Self::Error> {
+        usize::try_from(t)
+            .ok()
+            .and_then(Self::from_index)
+            .ok_or(UnknownTerrain(t))
+    }
+}
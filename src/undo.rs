@@ -0,0 +1,124 @@
+use std::collections::VecDeque;
+
+use crate::worldmap::WorldMap;
+
+// oldest moves fall off past this
+const MAX_UNDO: usize = 200;
+
+// Boards from before each move, newest last. Whole snapshots are cheap at
+// these board sizes and catch everything a move can touch: HP (the hero's
+// and wounded monsters'), item, inventory, bag, XP, perks, maxhp, counts,
+// game_over and the first click's re-deal. A move only gets a step if the
+// board after it differs from the one before.
+#[derive(Clone, Default)]
+pub struct History {
+    snapshots: VecDeque<WorldMap>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    // Runs a move, keeping the board from before it if undo is on and the
+    // move changed anything.
+    pub fn record(&mut self, world: &mut WorldMap, f: impl FnOnce(&mut WorldMap)) {
+        if !world.undo {
+            f(world);
+            return;
+        }
+
+        let before = world.clone();
+        f(world);
+        if *world != before {
+            if self.snapshots.len() == MAX_UNDO {
+                self.snapshots.pop_front();
+            }
+            self.snapshots.push_back(before);
+        }
+    }
+
+    // Puts the last move back. The undo itself still counts.
+    pub fn undo(&mut self, world: &mut WorldMap) -> bool {
+        if !world.undo {
+            return false;
+        }
+        let Some(before) = self.snapshots.pop_back() else {
+            return false;
+        };
+
+        // events still waiting belong to now, not to the snapshot
+        let undos = world.undos + 1;
+        let events = world.take_events();
+        *world = before;
+        world.undos = undos;
+        world.events = events;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::{self, Action};
+
+    // A board past its first click with undo on, and a closed monster
+    // toughened up so one blow can't drop it.
+    fn tough_monster() -> (WorldMap, History, usize, usize) {
+        let mut world = WorldMap::generate(30, 16, 120, 5).expect("default board deals");
+        world.undo = true;
+        let mut history = History::new();
+        replay::apply(&mut world, &mut history, Action::Open { x: 15, y: 8 });
+
+        let (x, y) = (0..world.maph)
+            .flat_map(|y| (0..world.mapw).map(move |x| (x, y)))
+            .find(|&(x, y)| !world.open[(x, y)] && world.entity(x, y).breed.is_some())
+            .expect("a closed monster");
+        let eid = world.entities[(x, y)];
+        world.entity_store[eid].hp = 100;
+        let heroid = world.entities[world.hero_pos];
+        world.entity_store[heroid].hp = 1000;
+        (world, history, x, y)
+    }
+
+    #[test]
+    fn undoes_a_fight_that_only_wounds() {
+        let (mut world, mut history, x, y) = tough_monster();
+        let steps = history.len();
+
+        replay::apply(&mut world, &mut history, Action::Open { x, y });
+        let hp = world.entity(x, y).hp;
+        assert!(hp > 0 && hp < 100);
+        // second blow on the open monster, nothing but HP moves
+        replay::apply(&mut world, &mut history, Action::Open { x, y });
+        assert!(world.entity(x, y).hp < hp);
+        assert_eq!(history.len(), steps + 2);
+
+        assert!(history.undo(&mut world));
+        assert_eq!(world.entity(x, y).hp, hp);
+        assert!(history.undo(&mut world));
+        assert_eq!(world.entity(x, y).hp, 100);
+        assert!(!world.open[(x, y)]);
+    }
+
+    #[test]
+    fn moves_that_change_nothing_leave_no_step() {
+        let (mut world, mut history, _, _) = tough_monster();
+        let steps = history.len();
+        // the first click's tile is already open
+        replay::apply(&mut world, &mut history, Action::Open { x: 15, y: 8 });
+        assert_eq!(history.len(), steps);
+    }
+}
@@ -1,221 +1,463 @@
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
 
-use macroquad::{
-    miniquad::date,
-    rand::{ChooseRandom, RandGenerator},
-    time,
-};
-
-use crate::entities::Entity;
+use crate::entities::{Breed, Consumable, Entity, Perk, Weapon};
 use crate::{
     entities,
-    items::{EFFECTIVE, INEFFECTIVE},
-    spawns::{SPAWN_ALLOWED, SPAWN_DIST},
+    event::Event,
+    grid::Grid,
+    mapgen::Generator,
+    placement::{self, GenError},
+    rng::Rng,
+    rules::{rules, Loot, Matchup},
+    solver,
+    terrain::{self, Terrain},
 };
 
+const NO_GUESS_REROLLS: usize = 20;
+
+// Flood fill working space. Not part of the board: never saved, and equal
+// on every board so a fill that opened nothing doesn't count as a change.
+#[derive(Clone, Default)]
+struct Scratch {
+    search_buffer: Vec<(usize, usize)>,
+    // a tile was visited by the current fill if it holds the current stamp
+    search_visited: Grid<u32>,
+    search_stamp: u32,
+    // tiles the last flood fill opened, in the order they opened
+    revealed: Vec<(usize, usize)>,
+}
+
+impl Scratch {
+    fn new(mapw: usize, maph: usize) -> Self {
+        Self {
+            search_buffer: vec![(0, 0); maph * mapw],
+            search_visited: Grid::new(mapw, maph, 0),
+            search_stamp: 0,
+            revealed: vec![],
+        }
+    }
+}
+
+impl PartialEq for Scratch {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct WorldMap {
     pub mapw: usize,
     pub maph: usize,
-    pub terrains: Vec<Vec<i16>>,
-    pub entities: Vec<Vec<usize>>,
-    pub auras: Vec<Vec<i16>>,
-    pub open: Vec<Vec<bool>>,
-    pub show_terrain: Vec<Vec<bool>>,
-    pub flags: Vec<Vec<i16>>,
+    pub terrains: Grid<Terrain>,
+    pub entities: Grid<usize>,
+    pub auras: Grid<i16>,
+    pub open: Grid<bool>,
+    pub show_terrain: Grid<bool>,
+    pub flags: Grid<i16>,
     pub game_over: u16,
     pub hero_pos: (usize, usize),
     pub entity_store: Vec<Entity>,
-    pub item: usize,
+    pub item: Weapon,
+    // weapons carried, item is one of them
+    #[serde(default)]
+    pub inventory: Vec<Weapon>,
+    // fights left in each carried weapon that can break
+    #[serde(default = "fresh_durability")]
+    pub durability: [i16; Weapon::COUNT],
+    // consumables carried, a count per kind
+    #[serde(default)]
+    pub bag: [u16; Consumable::COUNT],
+    // fights the ward still takes
+    #[serde(default)]
+    pub ward: i16,
+    // XP from kills, the level it bought and the perks picked along the way
+    #[serde(default)]
+    pub xp: u32,
+    #[serde(default = "first_level")]
+    pub hero_level: u16,
+    #[serde(default)]
+    pub perks: [u16; Perk::COUNT],
+    // level ups still waiting on a perk
+    #[serde(default)]
+    pub perks_pending: u16,
     pub maxhp: i16,
-    search_buffer: Vec<(usize, usize)>,
-    search_visited: HashSet<(usize, usize)>,
-    gen_pool: Vec<usize>,
-    gen_i: usize,
-    pub incomplete: bool,
+    #[serde(skip)]
+    scratch: Scratch,
+    // what happened since the last take_events
+    #[serde(skip)]
+    pub(crate) events: Vec<Event>,
+    pub(crate) gen_pool: Vec<usize>,
+    pub(crate) gen_i: usize,
     pub initialized: bool,
     pub counts: [i16; 10],
+    pub seed: u64,
+    pub no_guess: bool,
+    // custom per-breed counts, None keeps the ruleset share
+    #[serde(default)]
+    pub quota_overrides: [Option<usize>; 10],
+    #[serde(default)]
+    pub generator: Generator,
+    // undo allowed, and how often it was used
+    #[serde(default)]
+    pub undo: bool,
+    #[serde(default)]
+    pub undos: u32,
+    // seconds since the first click, and what the hero fought with
+    #[serde(default)]
+    pub play_time: f64,
+    #[serde(default)]
+    pub kills: [u32; 10],
+    #[serde(default)]
+    pub weapons_used: [u32; 10],
 }
 
-pub fn neighbors(x: usize, y: usize, w: usize, h: usize) -> impl Iterator<Item = (usize, usize)> {
-    return neighborsn(x as i16, y as i16, w as i16, h as i16, 1);
+fn first_level() -> u16 {
+    1
 }
 
-#[inline(always)]
-pub fn neighborsn(x: i16, y: i16, w: i16, h: i16, n: i16) -> impl Iterator<Item = (usize, usize)> {
-    (-n..=n).flat_map(move |dy| {
-        let yy = y + dy;
-        (-n..=n).filter_map(move |dx| {
-            if yy < 0 || yy >= h {
-                return None;
-            }
-
-            let xx = x + dx;
-            if xx < 0 || xx >= w {
-                None
-            } else {
-                Some((xx as usize, yy as usize))
-            }
-        })
-    })
+// every breakable weapon as good as new
+fn fresh_durability() -> [i16; Weapon::COUNT] {
+    let mut out = [0; Weapon::COUNT];
+    for w in Weapon::all() {
+        out[w.index()] = rules().durability(w).unwrap_or(0);
+    }
+    out
 }
 
 impl WorldMap {
     pub fn new(mapw: usize, maph: usize) -> Self {
-        let search_visited = HashSet::with_capacity(maph * mapw);
         let mut entity_store = Vec::with_capacity(mapw * maph);
         entity_store.push(entities::NONE);
-        entity_store.push(entities::MONSTERS[0]); // hero
+        entity_store.push(*rules().monster(Breed::HERO)); // hero
 
         Self {
             mapw,
             maph,
-            terrains: vec![vec![0; mapw]; maph],
-            entities: vec![vec![0; mapw]; maph],
-            auras: vec![vec![0; mapw]; maph],
-            open: vec![vec![false; mapw]; maph],
-            show_terrain: vec![vec![false; mapw]; maph],
-            flags: vec![vec![0; mapw]; maph],
+            terrains: Grid::new(mapw, maph, Terrain::Deep),
+            entities: Grid::new(mapw, maph, 0),
+            auras: Grid::new(mapw, maph, 0),
+            open: Grid::new(mapw, maph, false),
+            show_terrain: Grid::new(mapw, maph, false),
+            flags: Grid::new(mapw, maph, 0),
             entity_store,
             hero_pos: (0, 0),
-            item: 1,
+            item: Weapon::UNARMED,
+            inventory: vec![Weapon::UNARMED],
+            durability: fresh_durability(),
+            bag: [0; Consumable::COUNT],
+            ward: 0,
+            xp: 0,
+            hero_level: 1,
+            perks: [0; Perk::COUNT],
+            perks_pending: 0,
             game_over: 0,
             maxhp: 10,
-            search_buffer: vec![(0, 0); maph * mapw],
-            search_visited,
+            scratch: Scratch::new(mapw, maph),
+            events: vec![],
             gen_pool: (0..mapw * maph).collect(),
             gen_i: 0,
             initialized: false,
-            incomplete: true,
             counts: [0; 10],
+            seed: 0,
+            no_guess: false,
+            quota_overrides: [None; 10],
+            generator: Generator::Fissure,
+            undo: false,
+            undos: 0,
+            play_time: 0.,
+            kills: [0; 10],
+            weapons_used: [0; 10],
         }
     }
 
-    pub fn init(&mut self, mines: usize, seed_counter: u64) {
-        let rng = RandGenerator::new();
-        rng.srand(date::now() as u64 + seed_counter);
+    // Scratch space for flood fills isn't saved, size it back up after a load.
+    pub(crate) fn restore_scratch(&mut self) {
+        self.scratch = Scratch::new(self.mapw, self.maph);
+    }
 
-        self.gen_pool.shuffle_with_state(&rng);
-        let mut total = 0;
-        let mut count = 0;
+    // Builds the whole board from a single seed. Terrain, monster placement
+    // and the first-click remine all follow from it, so the same seed always
+    // gives the same game.
+    pub fn generate(mapw: usize, maph: usize, mines: usize, seed: u64) -> Result<Self, GenError> {
+        Self::generate_with(mapw, maph, mines, [None; 10], Generator::Fissure, seed)
+    }
 
-        let mut monster_bank = SPAWN_DIST.map(|x| (mines as f32 / x as f32).ceil() as i16);
-        println!("{:?}", monster_bank);
+    // generate with some breed counts pinned and a choice of landscape
+    pub fn generate_with(
+        mapw: usize,
+        maph: usize,
+        mines: usize,
+        quota_overrides: [Option<usize>; 10],
+        generator: Generator,
+        seed: u64,
+    ) -> Result<Self, GenError> {
+        if mapw.min(maph) < placement::MIN_SIDE {
+            return Err(GenError::BadSize { mapw, maph });
+        }
+        let mut rng = Rng::new(seed);
+
+        let mut w = Self::new(mapw, maph);
+        w.seed = seed;
+        w.quota_overrides = quota_overrides;
+        w.generator = generator;
+        w.set_terrain(generator.terrain(mapw, maph, &mut rng));
+        w.init(mines, &mut rng)?;
+        Ok(w)
+    }
 
-        while self.gen_i < self.gen_pool.len() && count < mines {
-            let n = self.gen_pool[self.gen_i];
-            self.gen_i += 1;
-            let y = n / self.mapw;
-            let x = n - y * self.mapw;
-            let t = self.terrains[y][x];
+    pub fn init(&mut self, mines: usize, rng: &mut Rng) -> Result<(), GenError> {
+        let tiles = self.mapw * self.maph;
+        // leave room for the first click to clear its 3x3
+        if mines + 9 > tiles {
+            return Err(GenError::BoardTooSmall {
+                tiles,
+                monsters: mines,
+            });
+        }
 
-            let mut spawn = 1;
-            for i in (0..9).rev() {
-                if monster_bank[i] == 0 {
-                    continue;
+        rng.shuffle(&mut self.gen_pool);
+        let quotas = placement::quotas_with(mines, &self.quota_overrides)?;
+
+        let mut terrain_counts = [0; terrain::COUNT];
+        for t in self.terrains.iter() {
+            terrain_counts[t.index()] += 1;
+        }
+
+        // bend terrain until the quotas fit, a tile at a time
+        let mut nudges = 0;
+        let mut plan = loop {
+            match placement::plan(&quotas, &terrain_counts) {
+                Ok(plan) => break plan,
+                Err(breed) if nudges < tiles => {
+                    self.nudge_terrain(breed, &mut terrain_counts);
+                    nudges += 1;
+                }
+                Err(breed) => {
+                    return Err(GenError::NoRoomForBreed {
+                        breed,
+                        quota: quotas[breed.index()],
+                    })
                 }
+            }
+        };
+
+        // walk the shuffled pool handing out the planned slots, bats go
+        // wherever nothing else is still needed
+        let mut bats = quotas[1];
+        let mut placed = Vec::with_capacity(mines);
+        let mut rest = Vec::with_capacity(tiles - mines);
+
+        for n in std::mem::take(&mut self.gen_pool) {
+            let y = n / self.mapw;
+            let x = n - y * self.mapw;
+            let t = self.terrains[(x, y)].index();
 
-                if SPAWN_ALLOWED[i + 1][t as usize] {
-                    monster_bank[i] -= 1;
-                    spawn = i + 1;
+            let mut spawn = None;
+            for (breed, row) in plan.iter_mut().enumerate().rev() {
+                if row[t] > 0 {
+                    row[t] -= 1;
+                    spawn = Breed::new(breed);
                     break;
                 }
             }
-            total += spawn;
-            count += 1;
+            if spawn.is_none() && bats > 0 {
+                bats -= 1;
+                spawn = Some(Breed::BAT);
+            }
+
+            let Some(spawn) = spawn else {
+                rest.push(n);
+                continue;
+            };
+
+            placed.push(n);
 
             let next_id = self.entity_store.len();
-            self.entity_store.push(entities::MONSTERS[spawn as usize]);
+            self.entity_store.push(*rules().monster(spawn));
             self.set_monster(x, y, next_id);
-            self.counts[spawn as usize] += 1;
+            self.counts[spawn.index()] += 1;
         }
 
-        self.incomplete = false;
-        for i in 1..9 {
-            if monster_bank[i] > 0 {
-                self.incomplete = true;
-                break;
+        // keep gen_pool as placed monsters followed by free tiles for remine
+        self.gen_i = placed.len();
+        placed.extend(rest);
+        self.gen_pool = placed;
+        Ok(())
+    }
+
+    // Turn one tile of the most common terrain that can't host the breed
+    // into the nearest terrain that can.
+    fn nudge_terrain(&mut self, breed: Breed, terrain_counts: &mut [usize; terrain::COUNT]) {
+        let Some(from) = Terrain::ALL
+            .into_iter()
+            .filter(|t| !t.allows(breed) && terrain_counts[t.index()] > 0)
+            .max_by_key(|t| terrain_counts[t.index()])
+        else {
+            return;
+        };
+        let to = placement::nearest_allowed(breed, from);
+
+        for &n in &self.gen_pool {
+            let y = n / self.mapw;
+            let x = n - y * self.mapw;
+            if self.terrains[(x, y)] == from {
+                self.terrains[(x, y)] = to;
+                self.show_terrain[(x, y)] = to.props().reveals;
+                terrain_counts[from.index()] -= 1;
+                terrain_counts[to.index()] += 1;
+                return;
             }
         }
-
-        println!("{:?}", monster_bank);
-        println!("{}/{}", self.evil_count().1, total);
     }
 
-    pub fn set_terrain(&mut self, terrains: Vec<Vec<i16>>) {
+    pub fn set_terrain(&mut self, terrains: Grid<Terrain>) {
+        self.show_terrain = terrains.map(|t| t.props().reveals);
         self.terrains = terrains;
-        for i in 0..self.maph {
-            for j in 0..self.mapw {
-                self.show_terrain[i][j] = self.terrains[i][j] == 9;
-            }
-        }
     }
 
     pub fn set_monster(&mut self, x: usize, y: usize, eid: usize) {
-        let old_idx = self.entities[y][x];
-        self.entities[y][x] = eid;
+        let old_idx = self.entities[(x, y)];
+        self.entities[(x, y)] = eid;
 
-        for (xx, yy) in neighbors(x, y, self.mapw, self.maph) {
+        for (xx, yy) in self.auras.neighbors(x, y) {
             // patch the difference for surrounding tile auras
-            self.auras[yy][xx] += self.entity_store[eid].level - self.entity_store[old_idx].level;
+            self.auras[(xx, yy)] += self.aura_level(eid) - self.aura_level(old_idx);
+        }
+    }
+
+    // what an entity adds to the auras around it, nothing once it's dead
+    #[inline(always)]
+    pub fn aura_level(&self, eid: usize) -> i16 {
+        let e = &self.entity_store[eid];
+        if e.hp > 0 {
+            e.level
+        } else {
+            0
+        }
+    }
+
+    // Rebuilds auras and breed counts from the living monsters, for saves
+    // made when the dead still counted until looted.
+    pub(crate) fn recount(&mut self) {
+        self.auras.fill(0);
+        self.counts = [0; 10];
+        for ((x, y), &eid) in self.entities.clone().positions() {
+            let level = self.aura_level(eid);
+            if level == 0 {
+                continue;
+            }
+            for (xx, yy) in self.auras.neighbors(x, y) {
+                self.auras[(xx, yy)] += level;
+            }
+            if let Some(breed) = self.entity_store[eid].breed {
+                self.counts[breed.index()] += 1;
+            }
         }
     }
 
     pub fn flag_tile_inc(&mut self, x: usize, y: usize) {
         // clamp x y
-        if x >= self.mapw || y >= self.maph {
+        if !self.open.contains(x, y) {
             return;
         }
 
-        if self.open[y][x] {
+        if self.open[(x, y)] {
             return;
         }
 
-        self.flags[y][x] = (self.flags[y][x] + 1) % 10;
+        self.flags[(x, y)] = (self.flags[(x, y)] + 1) % 10;
     }
 
     pub fn flag_tile(&mut self, x: usize, y: usize, num: i16) {
         // clamp x y
-        if x >= self.mapw || y >= self.maph {
+        if !self.open.contains(x, y) {
             return;
         }
 
-        if self.open[y][x] {
+        if self.open[(x, y)] {
             return;
         }
 
-        if self.flags[y][x] == num {
-            self.flags[y][x] = 0;
+        if self.flags[(x, y)] == num {
+            self.flags[(x, y)] = 0;
         } else {
-            self.flags[y][x] = num;
+            self.flags[(x, y)] = num;
+        }
+    }
+
+    // set a flag outright, for replays
+    pub fn set_flag(&mut self, x: usize, y: usize, num: i16) {
+        if !self.flags.contains(x, y) || self.open[(x, y)] {
+            return;
         }
+
+        self.flags[(x, y)] = num;
     }
 
     pub fn remine(&mut self, x: usize, y: usize) {
-        for (xx, yy) in neighbors(x, y, self.mapw, self.maph) {
-            let monidx = self.entities[yy][xx];
+        for (xx, yy) in self.open.neighbors(x, y) {
+            let monidx = self.entities[(xx, yy)];
             self.set_monster(xx, yy, 0);
             let mon = self.entity_store[monidx];
 
             if mon.level > 0 {
-                // don't take new values that are also adjacent
-                while self.gen_i < self.gen_pool.len() {
+                // don't take new values that are also adjacent, and prefer
+                // terrain the breed is allowed on
+                let free = self.gen_i..self.gen_pool.len();
+                let far = |n: usize| {
+                    let (j, i) = (n % self.mapw, n / self.mapw);
+                    i.abs_diff(y) > 1 || j.abs_diff(x) > 1
+                };
+                let allowed = |n: usize| {
+                    mon.breed
+                        .is_some_and(|b| self.terrains.as_slice()[n].allows(b))
+                };
+                let pick = free
+                    .clone()
+                    .find(|&k| far(self.gen_pool[k]) && allowed(self.gen_pool[k]))
+                    .or_else(|| free.clone().find(|&k| far(self.gen_pool[k])));
+
+                if let Some(k) = pick {
+                    self.gen_pool.swap(self.gen_i, k);
                     let n = self.gen_pool[self.gen_i];
-                    let i = n / self.mapw;
-                    let j = n - i * self.mapw;
                     self.gen_i += 1;
+                    self.set_monster(n % self.mapw, n / self.mapw, monidx);
+                }
+            }
+        }
+    }
 
-                    if (i as i16 - y as i16).abs() < 2 {
-                        continue;
-                    }
-                    if (j as i16 - x as i16).abs() < 2 {
-                        continue;
-                    }
+    fn first_click(&mut self, x: usize, y: usize) {
+        let mut rng = Rng::new(self.seed ^ (y * self.mapw + x) as u64);
+        let mines = self.entity_store.len() - 2;
 
-                    self.set_monster(j, i, monidx);
-                    break;
-                }
+        for _ in 0..NO_GUESS_REROLLS {
+            self.remine(x, y);
+            self.set_monster(x, y, 1);
+            self.hero_pos = (x, y);
+
+            if !self.no_guess || solver::make_solvable(self, x, y) {
+                return;
+            }
+
+            // repairs ran out of room, deal a new hand on the same terrain
+            let hand = self.clone();
+            self.entities.fill(0);
+            self.auras.fill(0);
+            self.entity_store.truncate(2);
+            self.counts = [0; 10];
+            self.gen_i = 0;
+            if self.init(mines, &mut rng).is_err() {
+                *self = hand;
+                break;
             }
         }
+
+        // out of hands, this one may need a guess somewhere
+        self.events.push(Event::NoGuessFailed);
+        self.remine(x, y);
+        self.set_monster(x, y, 1);
+        self.hero_pos = (x, y);
     }
 
     #[inline(always)]
@@ -226,109 +468,127 @@ impl WorldMap {
 
     #[inline(always)]
     pub fn entity(&self, x: usize, y: usize) -> &Entity {
-        let idx = self.entities[y][x];
+        let idx = self.entities[(x, y)];
         &self.entity_store[idx]
     }
 
+    // run the clock, only while a started game is still going
+    pub fn tick(&mut self, dt: f64) {
+        if self.initialized && self.game_over == 0 {
+            self.play_time += dt;
+        }
+    }
+
     pub fn end_game(&mut self, win: u16) {
         self.game_over = win;
 
-        for i in 0..self.maph {
-            for j in 0..self.mapw {
-                let idx = self.entities[i][j];
-                if self.entity_store[idx].level > 0 {
-                    self.open[i][j] = true;
-                }
+        for (open, &idx) in self.open.iter_mut().zip(self.entities.iter()) {
+            if self.entity_store[idx].level > 0 {
+                *open = true;
             }
         }
     }
 
-    fn open_tile_(&mut self, x: usize, y: usize) -> i32 {
+    // Flood fills out from (x, y) and returns the tiles it opened, nearest
+    // first.
+    fn open_tile_(&mut self, x: usize, y: usize) -> &[(usize, usize)] {
+        self.scratch.revealed.clear();
+
         // clamp x y
-        if x >= self.mapw || y >= self.maph {
-            return 0;
+        if !self.open.contains(x, y) {
+            return &[];
         }
 
         // move mines out of way for first click
         if !self.initialized {
-            self.remine(x, y);
-            self.set_monster(x, y, 1);
-            self.hero_pos = (x, y);
+            self.first_click(x, y);
         }
         self.initialized = true;
 
-        self.search_visited.clear();
-        let mut j = 0;
-        self.search_buffer[j] = (x, y);
-        self.search_visited.insert((x, y));
-
-        j += 1;
+        // a fresh stamp unvisits every tile, only wipe when it wraps around
+        self.scratch.search_stamp = self.scratch.search_stamp.wrapping_add(1);
+        if self.scratch.search_stamp == 0 {
+            self.scratch.search_visited.fill(0);
+            self.scratch.search_stamp = 1;
+        }
+        let stamp = self.scratch.search_stamp;
 
-        let mut opened = 0;
+        // breadth first through search_buffer as a queue, every tile goes in
+        // at most once so it never needs more than the board
+        let (mut head, mut tail) = (0, 1);
+        self.scratch.search_buffer[0] = (x, y);
+        self.scratch.search_visited[(x, y)] = stamp;
 
-        while j > 0 {
+        while head < tail {
             // shadow original tile vars
-            let (x, y) = self.search_buffer[j - 1];
-            j -= 1;
+            let (x, y) = self.scratch.search_buffer[head];
+            head += 1;
 
-            if self.open[y][x] {
+            if self.open[(x, y)] {
                 continue;
             };
 
-            self.open[y][x] = true;
-            let eid = self.entities[y][x];
+            self.open[(x, y)] = true;
+            let eid = self.entities[(x, y)];
             self.entity_store[eid].active = true;
-            opened += 1;
-            self.flags[y][x] = 0;
+            self.scratch.revealed.push((x, y));
+            self.events.push(Event::TileOpened { x, y });
+            self.flags[(x, y)] = 0;
+            if eid == 0 {
+                self.find(x, y);
+            }
 
             // hero.fight(self.monsters[y][x]);
 
-            if self.auras[y][x] > 0 {
-                for (xx, yy) in neighbors(x, y, self.mapw, self.maph) {
-                    self.show_terrain[yy][xx] = true;
+            if self.auras[(x, y)] > 0 {
+                for (xx, yy) in self.open.neighbors(x, y) {
+                    self.show_terrain[(xx, yy)] = true;
                 }
 
                 continue;
             }
 
-            for n in neighbors(x, y, self.mapw, self.maph) {
-                let (xx, yy) = n;
-                // skip self
-                if xx == x && yy == y {
-                    continue;
-                }
-                if self.open[yy][xx] {
+            // self is already stamped so it's skipped too
+            for (xx, yy) in self.open.neighbors(x, y) {
+                if self.open[(xx, yy)] || self.scratch.search_visited[(xx, yy)] == stamp {
                     continue;
                 }
-                if self.search_visited.contains(&n) {
-                    continue;
-                }
-                self.search_visited.insert(n);
+                self.scratch.search_visited[(xx, yy)] = stamp;
 
-                self.search_buffer[j] = n;
-                j += 1;
+                self.scratch.search_buffer[tail] = (xx, yy);
+                tail += 1;
             }
         }
 
-        opened
+        &self.scratch.revealed
+    }
+
+    // Hands over everything that happened since last time.
+    pub fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
     }
 
     pub fn open_tile(&mut self, x: usize, y: usize) -> bool {
         // possible for tile to be open based on another effect
-        if self.open[y][x] {
-            if self.entities[y][x] > 1 {
-                self.loot(x, y);
-                self.step(x, y);
+        if self.open[(x, y)] {
+            if self.entities[(x, y)] > 1 {
+                // a scroll can leave one open and still standing
+                if self.entity(x, y).hp > 0 {
+                    self.attack(x, y);
+                } else {
+                    self.loot(x, y);
+                }
+                self.step();
             }
 
             false
         } else {
-            let opened = self.open_tile_(x, y);
+            let opened = self.open_tile_(x, y).len();
 
             // step forward game if monster opened
-            if self.entities[y][x] > 1 {
+            if self.entities[(x, y)] > 1 {
                 self.attack(x, y);
-                self.step(x, y);
+                self.step();
             }
 
             opened > 0
@@ -336,91 +596,331 @@ impl WorldMap {
     }
 
     fn loot(&mut self, x: usize, y: usize) {
-        let eid = self.entities[y][x];
+        let eid = self.entities[(x, y)];
         let target = self.entity_store[eid];
-        let heroid = self.entities[self.hero_pos.1][self.hero_pos.0];
+        let heroid = self.entities[(self.hero_pos.0, self.hero_pos.1)];
 
-        if target.hp == 0 && target.breed >= 0 {
-            if target.breed == 1 {
-                if self.entity_store[heroid].hp < self.maxhp {
-                    self.entity_store[heroid].hp = self.maxhp.min(self.entity_store[heroid].hp + 2);
-                } else {
-                    self.entity_store[heroid].hp += 1;
-                    self.maxhp += 1;
+        let Some(breed) = target.breed else {
+            return;
+        };
+
+        if target.hp == 0 {
+            let loot = rules().loot(breed);
+            self.events.push(Event::ItemLooted { x, y, breed, loot });
+
+            let hp = self.entity_store[heroid].hp;
+            match loot {
+                Loot::Heal => {
+                    if hp < self.maxhp {
+                        self.entity_store[heroid].hp = self.maxhp.min(hp + 2);
+                    } else {
+                        self.entity_store[heroid].hp += 1;
+                        self.maxhp += 1;
+                        self.events.push(Event::MaxHpRaised { maxhp: self.maxhp });
+                    }
+                    self.events.push(Event::HpChanged {
+                        from: hp,
+                        to: self.entity_store[heroid].hp,
+                    });
+                }
+                Loot::Weapon(weapon) => self.pick_up(weapon),
+                Loot::Nothing => {}
+            }
+            for item in Consumable::ALL {
+                let rule = rules().consumable(item);
+                if rule.dropped_by.contains(&breed)
+                    && self.roll(x, y, Consumable::COUNT + item.index()) < rule.drop_chance
+                {
+                    self.bag[item.index()] += 1;
+                    self.events.push(Event::ItemFound { x, y, item });
                 }
-            } else {
-                self.item = target.breed as usize;
             }
             self.set_monster(x, y, 0);
+        }
+    }
+
+    // A roll in [0, 1) fixed by the seed, the tile and what it's for, so
+    // drops and finds come out the same on undo and in replays.
+    fn roll(&self, x: usize, y: usize, what: usize) -> f32 {
+        let tile = (what * self.mapw * self.maph + y * self.mapw + x) as u64;
+        let mut rng = Rng::new(self.seed ^ tile.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        rng.rand() as f32 / (u32::MAX as f32 + 1.)
+    }
 
-            self.counts[target.breed as usize] -= 1;
+    // at most one consumable turns up on a freshly opened empty tile
+    fn find(&mut self, x: usize, y: usize) {
+        let t = self.terrains[(x, y)].index();
+        for item in Consumable::ALL {
+            let rule = rules().consumable(item);
+            if rule.found_on[t] && self.roll(x, y, item.index()) < rule.find_chance {
+                self.bag[item.index()] += 1;
+                self.events.push(Event::ItemFound { x, y, item });
+                return;
+            }
         }
     }
 
+    // Uses up one of a consumable, aimed at (x, y) if it needs a tile. False
+    // if there's none left or it would do nothing.
+    pub fn use_item(&mut self, item: Consumable, x: usize, y: usize) -> bool {
+        if self.game_over != 0
+            || !self.initialized
+            || self.bag[item.index()] == 0
+            || !self.open.contains(x, y)
+        {
+            return false;
+        }
+
+        let power = rules().consumable(item).power;
+        let heroid = self.entities[(self.hero_pos.0, self.hero_pos.1)];
+        match item {
+            Consumable::Potion => {
+                let hp = self.entity_store[heroid].hp;
+                if hp >= self.maxhp {
+                    return false;
+                }
+                let to = self.maxhp.min(hp + power);
+                self.entity_store[heroid].hp = to;
+                self.events.push(Event::ItemUsed { x, y, item });
+                self.events.push(Event::HpChanged { from: hp, to });
+            }
+            Consumable::Scroll => {
+                let square: Vec<_> = self.open.neighborsn(x, y, power as usize).collect();
+                if square.iter().all(|&p| self.open[p]) {
+                    return false;
+                }
+                self.events.push(Event::ItemUsed { x, y, item });
+                // monsters come up open but unfought
+                for (xx, yy) in square {
+                    self.open_tile_(xx, yy);
+                }
+            }
+            Consumable::Fog => {
+                let square: Vec<_> = self.open.neighborsn(x, y, power as usize).collect();
+                if square.iter().all(|&p| self.open[p] || self.show_terrain[p]) {
+                    return false;
+                }
+                self.events.push(Event::ItemUsed { x, y, item });
+                for p in square {
+                    self.show_terrain[p] = true;
+                }
+            }
+            Consumable::Ward => {
+                self.events.push(Event::ItemUsed { x, y, item });
+                self.ward += power;
+            }
+        }
+
+        self.bag[item.index()] -= 1;
+        true
+    }
+
+    // Takes a weapon into a free slot, or in place of the one in hand when
+    // the slots are full, and holds it.
+    fn pick_up(&mut self, weapon: Weapon) {
+        if !self.inventory.contains(&weapon) {
+            let slot = self.inventory.iter().position(|&w| w == self.item);
+            match slot {
+                Some(slot) if self.inventory.len() >= rules().slots() => {
+                    self.inventory[slot] = weapon;
+                }
+                _ => self.inventory.push(weapon),
+            }
+        }
+        // a new one comes undamaged
+        if let Some(d) = rules().durability(weapon) {
+            self.durability[weapon.index()] = d;
+        }
+        self.item = weapon;
+    }
+
+    // The weapon in hand is gone, back to bare hands.
+    fn break_weapon(&mut self) {
+        let weapon = self.item;
+        if let Some(slot) = self.inventory.iter().position(|&w| w == weapon) {
+            self.inventory.remove(slot);
+        }
+        if !self.inventory.contains(&Weapon::UNARMED) {
+            self.inventory.insert(0, Weapon::UNARMED);
+        }
+        self.item = Weapon::UNARMED;
+        self.events.push(Event::WeaponBroke { weapon });
+    }
+
+    // Kills add up to levels, each owed a perk.
+    fn gain_xp(&mut self, xp: u32) {
+        self.xp += xp;
+        while let Some(next) = rules().xp_for(self.hero_level) {
+            if self.xp < next {
+                break;
+            }
+            self.hero_level += 1;
+            self.perks_pending += 1;
+            self.events.push(Event::LeveledUp {
+                level: self.hero_level,
+            });
+        }
+    }
+
+    // XP at the start of this level and the total for the next, None at the
+    // top
+    pub fn xp_range(&self) -> (u32, Option<u32>) {
+        let from = match self.hero_level {
+            0 | 1 => 0,
+            level => rules().xp_for(level - 1).unwrap_or(0),
+        };
+        (from, rules().xp_for(self.hero_level))
+    }
+
+    // Spends a level up. False if none is waiting.
+    pub fn choose_perk(&mut self, perk: Perk) -> bool {
+        if self.perks_pending == 0 || self.game_over != 0 {
+            return false;
+        }
+        self.perks_pending -= 1;
+        self.perks[perk.index()] += 1;
+        self.events.push(Event::PerkChosen { perk });
+
+        let heroid = self.entities[(self.hero_pos.0, self.hero_pos.1)];
+        let hp = self.entity_store[heroid].hp;
+        match perk {
+            Perk::Vitality => {
+                self.maxhp += 2;
+                self.entity_store[heroid].hp += 2;
+                self.events.push(Event::MaxHpRaised { maxhp: self.maxhp });
+            }
+            Perk::Toughness => {}
+            Perk::SecondWind => self.entity_store[heroid].hp = hp.max(self.maxhp),
+        }
+        let to = self.entity_store[heroid].hp;
+        if to != hp {
+            self.events.push(Event::HpChanged { from: hp, to });
+        }
+        true
+    }
+
+    // Holds the weapon in that slot. False if there's nothing to switch to.
+    pub fn equip(&mut self, slot: usize) -> bool {
+        if self.game_over != 0 {
+            return false;
+        }
+        match self.inventory.get(slot) {
+            Some(&weapon) if weapon != self.item => {
+                self.item = weapon;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn equipped_slot(&self) -> Option<usize> {
+        self.inventory.iter().position(|&w| w == self.item)
+    }
+
     fn attack(&mut self, x: usize, y: usize) {
-        let eid = self.entities[y][x];
+        let eid = self.entities[(x, y)];
         let target = self.entity_store[eid];
-        let heroid = self.entities[self.hero_pos.1][self.hero_pos.0];
+        let heroid = self.entities[(self.hero_pos.0, self.hero_pos.1)];
+
+        let Some(breed) = target.breed else {
+            return;
+        };
 
         if eid > 1 {
-            match self.item {
-                0..=9 => {
-                    let ineff = INEFFECTIVE[self.item];
-                    let eff = EFFECTIVE[self.item];
-
-                    if ineff.contains(&target.breed) {
-                        self.entity_store[heroid].hp -= 2 * self.entity_store[eid].breed;
-                    } else if eff.contains(&target.breed) {
-                        // if self.entity_store[heroid].hp < self.maxhp {
-                        //     self.entity_store[heroid].hp += 1;
-                        // }
-                    } else {
-                        self.entity_store[heroid].hp -= self.entity_store[eid].breed;
-                    }
+            let matchup = rules().matchup(self.item, breed);
+            let hp = self.entity_store[heroid].hp;
+            let warded = self.ward > 0;
+            let tough = self.perks[Perk::Toughness.index()] as i16;
+
+            match matchup {
+                _ if warded => self.ward -= 1,
+                Matchup::Ineffective => {
+                    self.entity_store[heroid].hp -= (2 * target.level - tough).max(0)
+                }
+                Matchup::Effective => {
+                    // if self.entity_store[heroid].hp < self.maxhp {
+                    //     self.entity_store[heroid].hp += 1;
+                    // }
+                }
+                Matchup::Neutral => self.entity_store[heroid].hp -= (target.level - tough).max(0),
+            }
+
+            // the hero's blow, a survivor stays up for another round
+            let left = (target.hp - rules().blow(self.item, matchup)).max(0);
+            self.entity_store[eid].hp = left;
+            self.weapons_used[self.item.index()] += 1;
+
+            let to = self.entity_store[heroid].hp;
+            self.events.push(Event::MonsterFought {
+                x,
+                y,
+                breed,
+                weapon: self.item,
+                matchup,
+                damage: hp - to,
+                warded,
+                left,
+            });
+            if to != hp {
+                self.events.push(Event::HpChanged { from: hp, to });
+            }
+
+            // dead ones stop counting right away, looting just clears the tile
+            if left == 0 {
+                for (xx, yy) in self.auras.neighbors(x, y) {
+                    self.auras[(xx, yy)] -= target.level;
+                }
+                self.counts[breed.index()] -= 1;
+                self.kills[breed.index()] += 1;
+                self.gain_xp(target.level as u32);
+            }
 
-                    // kill off monster
-                    self.entity_store[eid].hp = 0;
+            // every fight wears the weapon, bad matchups more
+            if rules().durability(self.item).is_some() {
+                let left = &mut self.durability[self.item.index()];
+                *left -= rules().wear(self.item, matchup);
+                if *left <= 0 {
+                    self.break_weapon();
                 }
-                _ => {}
             }
         }
     }
 
     pub fn chord_tile(&mut self, x: usize, y: usize) {
         // prevent chording on cloud tiles
-        if self.terrains[y][x] == 8 {
+        if self.terrains[(x, y)].props().blocks_chord {
             return;
         }
 
         // only chord open tiles
-        if !self.open[y][x] {
+        if !self.open[(x, y)] {
             return;
         }
 
         // tile must have some enemy nearby
-        let aura = self.auras[y][x];
+        let aura = self.auras[(x, y)];
         if aura < 1 {
             return;
         }
 
         // sum current values
         let mut sum = 0;
-        for (xx, yy) in neighbors(x, y, self.mapw, self.maph) {
+        for (xx, yy) in self.open.neighbors(x, y) {
             // handle open tile cases
-            let eid = self.entities[yy][xx];
-            if self.open[yy][xx] {
-                sum += self.entity_store[eid].level;
+            let eid = self.entities[(xx, yy)];
+            if self.open[(xx, yy)] {
+                sum += self.aura_level(eid);
             } else {
-                sum += self.flags[yy][xx];
+                sum += self.flags[(xx, yy)];
             }
         }
 
         if sum == aura {
-            for (xx, yy) in neighbors(x, y, self.mapw, self.maph) {
-                if self.flags[yy][xx] > 0 {
+            for (xx, yy) in self.open.neighbors(x, y) {
+                if self.flags[(xx, yy)] > 0 {
                     continue;
                 }
-                if self.open[yy][xx] {
+                if self.open[(xx, yy)] {
                     continue;
                 }
 
@@ -439,13 +939,14 @@ impl WorldMap {
                 evil_sum += i as i16 * value;
             }
         }
-        return (evil_count, evil_sum);
+        (evil_count, evil_sum)
     }
 
-    pub fn step(&mut self, x: usize, y: usize) {
-        let heroid = self.entities[self.hero_pos.1][self.hero_pos.0];
+    pub fn step(&mut self) {
+        let heroid = self.entities[(self.hero_pos.0, self.hero_pos.1)];
 
         let (evil_count, _) = self.evil_count();
+        let was_over = self.game_over;
 
         if self.entity_store[heroid].hp < 1 {
             self.end_game(2);
@@ -454,5 +955,97 @@ impl WorldMap {
         if evil_count == 0 {
             self.end_game(1);
         }
+
+        // a last hit that also clears the board counts as the win
+        if self.game_over != was_over {
+            self.events.push(match self.game_over {
+                1 => Event::GameWon,
+                _ => Event::GameLost,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_guess_failed(w: usize, h: usize, mines: usize, seed: u64) -> bool {
+        let mut world = WorldMap::generate(w, h, mines, seed).expect("board deals");
+        world.no_guess = true;
+        world.open_tile(w / 2, h / 2);
+        assert!(world.initialized);
+        world
+            .take_events()
+            .iter()
+            .any(|e| matches!(e, Event::NoGuessFailed))
+    }
+
+    // A no-guess first click says so when it can't deliver, rather than
+    // quietly handing over a board that needs a guess.
+    #[test]
+    fn no_guess_reports_when_no_deal_works() {
+        // too crammed for any hand to come out guess-free
+        assert!(no_guess_failed(8, 8, 45, 2));
+    }
+
+    // a default board deals guess-free even with monsters that take
+    // several blows
+    #[test]
+    fn no_guess_deals_a_default_board() {
+        assert!(!no_guess_failed(30, 16, 120, 3));
+    }
+
+    // Library generation has no side cap, only the custom form does.
+    #[test]
+    fn deals_a_board_of_hundreds_of_thousands_of_tiles() {
+        let (w, h) = (600, 500);
+        let mut world = WorldMap::generate(w, h, w * h / 5, 7).expect("big board deals");
+        assert_eq!(world.terrains.width(), w);
+        assert_eq!(world.entities.height(), h);
+
+        world.open_tile(w / 2, h / 2);
+        assert!(world.initialized);
+        assert_eq!(world.game_over, 0);
+        assert!(world.open[(w / 2, h / 2)]);
+    }
+
+    // a side longer than an i16 reaches
+    #[test]
+    fn deals_a_board_with_a_very_long_side() {
+        let (w, h) = (40_000, 6);
+        let mut world = WorldMap::generate(w, h, w * h / 8, 7).expect("long board deals");
+        world.open_tile(w - 2, 3);
+        assert!(world.initialized);
+        assert!(world.open[(w - 2, 3)]);
+        assert_eq!(world.hero_pos, (w - 2, 3));
+    }
+
+    // a fog-lift over ground already in sight stays in the bag
+    #[test]
+    fn fog_lift_needs_something_to_lift() {
+        let mut world = WorldMap::generate(30, 16, 120, 3).expect("default board deals");
+        world.open_tile(15, 8);
+        world.take_events();
+        world.bag[Consumable::Fog.index()] = 1;
+
+        world.show_terrain.fill(true);
+        assert!(!world.use_item(Consumable::Fog, 15, 8));
+        assert_eq!(world.bag[Consumable::Fog.index()], 1);
+        assert!(world.take_events().is_empty());
+
+        let hidden = (0..30)
+            .map(|x| (x, 0))
+            .find(|&p| !world.open[p])
+            .expect("a closed tile on the top row");
+        world.show_terrain[hidden] = false;
+        assert!(world.use_item(Consumable::Fog, hidden.0, hidden.1));
+        assert_eq!(world.bag[Consumable::Fog.index()], 0);
+        assert!(world.show_terrain[hidden]);
+    }
+
+    #[test]
+    fn rejects_sides_under_the_minimum() {
+        assert!(WorldMap::generate(4, 30, 5, 1).is_err());
     }
 }